@@ -0,0 +1,182 @@
+// Generates the ROM database lookup table in $OUT_DIR/rom_database.rs from
+// roms/database, which follows the layout of the community CHIP-8 program
+// database at https://github.com/chip-8/chip-8-database.
+//
+// The copy in roms/database only lists the ROMs bundled in this repository. To
+// recognise everything the community knows about, point CHIP8RS_ROM_DATABASE at
+// the database directory of a chip-8-database checkout when building.
+use serde::Deserialize;
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  env,
+  fmt::Write,
+  fs,
+  path::{Path, PathBuf},
+};
+
+const DATABASE_VAR: &str = "CHIP8RS_ROM_DATABASE";
+const BUNDLED_DATABASE: &str = "roms/database";
+const PROGRAMS: &str = "programs.json";
+const PLATFORMS: &str = "platforms.json";
+
+#[derive(Deserialize)]
+struct Program {
+  title: String,
+  roms: BTreeMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+  platforms: Vec<String>,
+  tickrate: Option<usize>,
+  #[serde(default)]
+  quirky_platforms: BTreeMap<String, QuirkOverrides>,
+  colors: Option<Colors>,
+  #[serde(default)]
+  keys: BTreeMap<String, u8>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+  #[serde(default)]
+  pixels: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+  id: String,
+  quirks: PlatformQuirks,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlatformQuirks {
+  shift: bool,
+  memory_increment_by_x: bool,
+  memory_leave_i_unchanged: bool,
+  wrap: bool,
+  jump: bool,
+  vblank: bool,
+  logic: bool,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+  shift: Option<bool>,
+  memory_increment_by_x: Option<bool>,
+  memory_leave_i_unchanged: Option<bool>,
+  wrap: Option<bool>,
+  jump: Option<bool>,
+  vblank: Option<bool>,
+  logic: Option<bool>,
+}
+
+impl PlatformQuirks {
+  fn with(mut self, overrides: &QuirkOverrides) -> Self {
+    let QuirkOverrides { shift, memory_increment_by_x, memory_leave_i_unchanged, wrap, jump, vblank, logic } =
+      *overrides;
+    self.shift = shift.unwrap_or(self.shift);
+    self.memory_increment_by_x = memory_increment_by_x.unwrap_or(self.memory_increment_by_x);
+    self.memory_leave_i_unchanged = memory_leave_i_unchanged.unwrap_or(self.memory_leave_i_unchanged);
+    self.wrap = wrap.unwrap_or(self.wrap);
+    self.jump = jump.unwrap_or(self.jump);
+    self.vblank = vblank.unwrap_or(self.vblank);
+    self.logic = logic.unwrap_or(self.logic);
+    self
+  }
+
+  // Sprites that don't wrap are clipped
+  fn to_rust(self) -> String {
+    format!(
+      "Quirks {{ vf_reset: {}, key_release: true, memory: {}, memory_increment_by_x: {}, clipping: {}, \
+       shifting: {}, display_wait: {}, jumping: {} }}",
+      self.logic,
+      !self.memory_leave_i_unchanged,
+      self.memory_increment_by_x,
+      !self.wrap,
+      self.shift,
+      self.vblank,
+      self.jump,
+    )
+  }
+}
+
+// Platforms the interpreter can run, and the variant each one needs
+fn variant(platform: &str) -> Option<&'static str> {
+  match platform {
+    "originalChip8" | "hybridVIP" | "modernChip8" => Some("Variant::Chip8"),
+    "chip48" | "superchip1" | "superchip" => Some("Variant::SuperChip"),
+    "xochip" => Some("Variant::XoChip"),
+    _ => None,
+  }
+}
+
+fn color(hex: &str) -> String {
+  let rgb = hex
+    .strip_prefix('#')
+    .filter(|hex| hex.len() == 6)
+    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+    .unwrap_or_else(|| panic!("Invalid colour {hex} in {PROGRAMS}"));
+  let [_, r, g, b] = rgb.to_be_bytes();
+  format!("[{r:#04X}, {g:#04X}, {b:#04X}, 0xFF]")
+}
+
+fn read<T: for<'de> Deserialize<'de>>(database: &Path, file: &str) -> T {
+  let path = database.join(file);
+  println!("cargo:rerun-if-changed={}", path.display());
+  let text = fs::read_to_string(&path).unwrap_or_else(|err| panic!("Unable to read {}: {err}", path.display()));
+  serde_json::from_str(&text).unwrap_or_else(|err| panic!("Invalid {}: {err}", path.display()))
+}
+
+fn main() {
+  println!("cargo:rerun-if-env-changed={DATABASE_VAR}");
+  let database = env::var_os(DATABASE_VAR).map_or_else(|| PathBuf::from(BUNDLED_DATABASE), PathBuf::from);
+  let programs: Vec<Program> = read(&database, PROGRAMS);
+  let platforms: Vec<Platform> = read(&database, PLATFORMS);
+  let quirks: BTreeMap<_, _> = platforms.iter().map(|platform| (platform.id.as_str(), platform.quirks)).collect();
+
+  let mut map = phf_codegen::Map::new();
+  let mut seen = BTreeSet::new();
+  for program in &programs {
+    for (hash, rom) in &program.roms {
+      // ROMs made for platforms the interpreter doesn't support are left out
+      let Some((platform, variant)) = rom
+        .platforms
+        .iter()
+        .find_map(|platform| variant(platform).map(|variant| (platform.as_str(), variant)))
+      else {
+        continue;
+      };
+      let platform_quirks = quirks
+        .get(platform)
+        .unwrap_or_else(|| panic!("{platform} is missing from {PLATFORMS}"));
+      let overrides = rom.quirky_platforms.get(platform).copied().unwrap_or_default();
+      let quirks = platform_quirks.with(&overrides);
+
+      let mut entry = String::new();
+      write!(entry, "RomInfo {{ title: {:?}, variant: {variant}, quirks: {}, ", program.title, quirks.to_rust()).unwrap();
+      write!(entry, "tickrate: {:?}, colors: &[", rom.tickrate).unwrap();
+      for pixel in rom.colors.iter().flat_map(|colors| &colors.pixels) {
+        write!(entry, "{}, ", color(pixel)).unwrap();
+      }
+      entry.push_str("], keys: &[");
+      for (action, key) in &rom.keys {
+        assert!(*key < 16, "Invalid key {key} for {hash} in {PROGRAMS}");
+        write!(entry, "({action:?}, {key}), ").unwrap();
+      }
+      entry.push_str("] }");
+
+      let hash = hash.to_ascii_lowercase();
+      if !seen.insert(hash.clone()) {
+        panic!("{hash} is listed twice in {PROGRAMS}");
+      }
+      map.entry(hash, &entry);
+    }
+  }
+
+  let code = format!("static ROMS: phf::Map<&'static str, RomInfo> = {};\n", map.build());
+  let out = Path::new(&env::var("OUT_DIR").unwrap()).join("rom_database.rs");
+  fs::write(out, code).unwrap();
+}
@@ -0,0 +1,1206 @@
+// An assembler for Octo (https://github.com/JohnEarnest/Octo) sources. It lays
+// programs out the way Octo does, down to the reserved jump to main and the
+// instructions the structured forms compile to, so a .8o file assembles to the
+// same bytes as the .ch8 Octo would have written.
+use super::memory::ROM_OFFSET;
+use alloc::{
+  collections::{BTreeMap, VecDeque},
+  format,
+  string::{String, ToString},
+  vec,
+  vec::Vec,
+};
+use core::fmt;
+
+// Highest address an XO-CHIP program can reach
+const MAX_ADDRESS: usize = 0xFFFF;
+// Macros nested deeper than this are taken to be expanding themselves
+const MAX_EXPANSION_DEPTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssemblerError {
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for AssemblerError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Line {}: {}", self.line, self.message)
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AssemblerError {}
+
+type AssemblerResult<T = ()> = Result<T, AssemblerError>;
+
+// Compiles an Octo program to a ROM image to load at 0x200
+pub fn assemble(source: &str) -> AssemblerResult<Vec<u8>> {
+  Ok(assemble_with_symbols(source)?.0)
+}
+
+// Compiles a program along with what a debugger needs to map it back to the
+// source
+pub fn assemble_with_symbols(source: &str) -> AssemblerResult<(Vec<u8>, Symbols)> {
+  Assembler::new(tokenize(source)?).run()
+}
+
+// Where a program's instructions and labels came from. Instructions a macro
+// expands to belong to the line it's used on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Symbols {
+  // The address of each instruction and its line, in the order they were
+  // written
+  pub lines: Vec<(u16, usize)>,
+  pub labels: BTreeMap<String, u16>,
+}
+
+impl Symbols {
+  // The first instruction on the line, or on the next line with any, for a
+  // breakpoint set on a comment or blank line. Returns the line it's on too.
+  pub fn address(&self, line: usize) -> Option<(u16, usize)> {
+    self
+      .lines
+      .iter()
+      .filter(|&&(_, at)| at >= line)
+      .min_by_key(|&&(address, at)| (at, address))
+      .copied()
+  }
+
+  // The line the instruction at the address came from
+  pub fn line(&self, address: u16) -> Option<usize> {
+    self.lines.iter().find(|&&(at, _)| at == address).map(|&(_, line)| line)
+  }
+
+  // The closest label at or before the address, e.g. the subroutine it's in
+  pub fn label(&self, address: u16) -> Option<&str> {
+    self
+      .labels
+      .iter()
+      .filter(|&(_, &at)| at <= address)
+      .max_by_key(|&(_, &at)| at)
+      .map(|(name, _)| name.as_str())
+  }
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+  text: String,
+  // Quoted strings are kept apart from words, so "{" is never a brace
+  string: bool,
+  line: usize,
+  // How many macro or string mode expansions the token came out of
+  depth: usize,
+}
+
+impl Token {
+  fn word(text: String, line: usize) -> Self {
+    Self { text, string: false, line, depth: 0 }
+  }
+
+  fn is(&self, word: &str) -> bool {
+    !self.string && self.text == word
+  }
+}
+
+// Splits a source into whitespace separated words and quoted strings,
+// dropping # comments
+fn tokenize(source: &str) -> AssemblerResult<VecDeque<Token>> {
+  let mut tokens = VecDeque::new();
+  let mut chars = source.chars().peekable();
+  let mut line = 1;
+  while let Some(c) = chars.next() {
+    match c {
+      '\n' => line += 1,
+      // Editors on Windows like to start files with a byte order mark
+      '\u{FEFF}' => {}
+      c if c.is_whitespace() => {}
+      '#' => {
+        while chars.next_if(|&c| c != '\n').is_some() {}
+      }
+      '"' => {
+        let mut text = String::new();
+        loop {
+          let c = match chars.next() {
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+              Some('t') => '\t',
+              Some('n') => '\n',
+              Some('r') => '\r',
+              Some('v') => '\x0B',
+              Some('0') => '\0',
+              Some(c @ ('\\' | '"')) => c,
+              Some(c) => return Err(AssemblerError { line, message: format!("Unknown escape \\{c} in string") }),
+              None => break,
+            },
+            Some('\n') | None => {
+              return Err(AssemblerError { line, message: "Missing the closing \" of a string".into() })
+            }
+            Some(c) => c,
+          };
+          text.push(c);
+        }
+        tokens.push_back(Token { text, string: true, line, depth: 0 });
+      }
+      c => {
+        let mut text = String::from(c);
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+          text.push(c);
+        }
+        tokens.push_back(Token::word(text, line));
+      }
+    }
+  }
+  Ok(tokens)
+}
+
+// Reads a number the way Octo writes them: decimal, 0x hex or 0b binary, with
+// an optional minus sign
+fn parse_number(text: &str) -> Option<i64> {
+  let (negative, digits) = match text.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, text),
+  };
+  let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+    i64::from_str_radix(hex, 16).ok()?
+  } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+    i64::from_str_radix(binary, 2).ok()?
+  } else if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+    digits.parse().ok()?
+  } else {
+    return None;
+  };
+  Some(if negative { -value } else { value })
+}
+
+// v0 to vF, in either case
+fn parse_register(text: &str) -> Option<u8> {
+  let digit = text.strip_prefix(['v', 'V'])?;
+  match digit.len() {
+    1 => u8::from_str_radix(digit, 16).ok(),
+    _ => None,
+  }
+}
+
+// How a use of a label that isn't defined yet gets filled in once it is
+#[derive(Clone, Copy, Debug)]
+enum Patch {
+  // The low 12 bits of an instruction like JP or LD I
+  Address,
+  // Two bytes, for i := long and :pointer
+  Wide,
+  // The pair of loads :unpack writes, with the nibble it puts on top
+  Unpack(u8),
+  // The pair of loads :unpack long writes
+  UnpackLong,
+}
+
+struct Macro {
+  args: Vec<String>,
+  body: Vec<Token>,
+  // How many times it's been expanded, which the body can use as CALLS
+  calls: usize,
+}
+
+struct Loop {
+  start: usize,
+  // The jumps out of the loop each while compiles to
+  exits: Vec<usize>,
+  line: usize,
+}
+
+struct Assembler {
+  tokens: VecDeque<Token>,
+  // Line of the token being compiled, for errors
+  line: usize,
+  rom: Vec<u8>,
+  pc: usize,
+  // Octo starts every program with a jump to main, unless main comes first
+  jump_to_main: bool,
+  // Whether an :org has moved the PC, which keeps the jump to main in place
+  org: bool,
+  labels: BTreeMap<String, usize>,
+  constants: BTreeMap<String, f64>,
+  aliases: BTreeMap<String, u8>,
+  // Where each label that isn't defined yet is used, and on which line
+  forward: BTreeMap<String, Vec<(usize, Patch, usize)>>,
+  macros: BTreeMap<String, Macro>,
+  // Each string mode's body for each character it knows, and the character's
+  // index in the alphabet
+  string_modes: BTreeMap<String, BTreeMap<char, (usize, Vec<Token>)>>,
+  // The jumps if..begin and else compile to, patched at the else or end
+  branches: Vec<(usize, usize)>,
+  loops: Vec<Loop>,
+  lines: Vec<(u16, usize)>,
+}
+
+impl Assembler {
+  fn new(tokens: VecDeque<Token>) -> Self {
+    let aliases = [("compare-temp", 0xF), ("unpack-hi", 0x0), ("unpack-lo", 0x1)];
+    Self {
+      tokens,
+      line: 1,
+      rom: vec![0, 0],
+      pc: ROM_OFFSET + 2,
+      jump_to_main: true,
+      org: false,
+      labels: BTreeMap::new(),
+      constants: BTreeMap::new(),
+      aliases: aliases.into_iter().map(|(name, register)| (name.to_string(), register)).collect(),
+      forward: BTreeMap::new(),
+      macros: BTreeMap::new(),
+      string_modes: BTreeMap::new(),
+      branches: Vec::new(),
+      loops: Vec::new(),
+      lines: Vec::new(),
+    }
+  }
+
+  fn error<T>(&self, message: impl Into<String>) -> AssemblerResult<T> {
+    Err(AssemblerError { line: self.line, message: message.into() })
+  }
+
+  fn run(mut self) -> AssemblerResult<(Vec<u8>, Symbols)> {
+    while let Some(token) = self.tokens.pop_front() {
+      self.line = token.line;
+      self.statement(token)?;
+    }
+
+    if let Some(&(_, line)) = self.branches.last() {
+      self.line = line;
+      return self.error("This begin is missing its end");
+    }
+    if let Some(open) = self.loops.last() {
+      self.line = open.line;
+      return self.error("This loop is missing its again");
+    }
+    if let Some((name, uses)) = self.forward.iter().next() {
+      self.line = uses[0].2;
+      return self.error(format!("Undefined name {name}"));
+    }
+    if self.jump_to_main {
+      let Some(&main) = self.labels.get("main") else {
+        return self.error("The program is missing a main label");
+      };
+      self.patch(ROM_OFFSET, Patch::Address, main)?;
+      self.rom[0] |= 0x10;
+    }
+    let labels = self.labels.into_iter().map(|(name, address)| (name, address as u16)).collect();
+    Ok((self.rom, Symbols { lines: self.lines, labels }))
+  }
+
+  fn next(&mut self) -> AssemblerResult<Token> {
+    match self.tokens.pop_front() {
+      Some(token) => {
+        self.line = token.line;
+        Ok(token)
+      }
+      None => self.error("Unexpected end of file"),
+    }
+  }
+
+  fn peek(&self, index: usize) -> Option<&str> {
+    self.tokens.get(index).filter(|token| !token.string).map(|token| token.text.as_str())
+  }
+
+  fn expect(&mut self, word: &str) -> AssemblerResult {
+    let token = self.next()?;
+    if !token.is(word) {
+      return self.error(format!("Expected {word}, found {}", token.text));
+    }
+    Ok(())
+  }
+
+  fn name(&mut self) -> AssemblerResult<String> {
+    let token = self.next()?;
+    if token.string || parse_number(&token.text).is_some() || self.is_register(&token.text) {
+      return self.error(format!("Expected a name, found {}", token.text));
+    }
+    Ok(token.text)
+  }
+
+  fn string(&mut self) -> AssemblerResult<String> {
+    let token = self.next()?;
+    if !token.string {
+      return self.error(format!("Expected a string, found {}", token.text));
+    }
+    Ok(token.text)
+  }
+
+  // The tokens between a { and its matching }
+  fn block(&mut self) -> AssemblerResult<Vec<Token>> {
+    self.expect("{")?;
+    let mut body = Vec::new();
+    let mut depth = 0;
+    loop {
+      let token = self.next()?;
+      if token.is("{") {
+        depth += 1;
+      } else if token.is("}") {
+        if depth == 0 {
+          return Ok(body);
+        }
+        depth -= 1;
+      }
+      body.push(token);
+    }
+  }
+
+  // Runs tokens a call expands to before the rest of the source
+  fn expand(&mut self, call: &Token, tokens: Vec<Token>) -> AssemblerResult {
+    if call.depth >= MAX_EXPANSION_DEPTH {
+      return self.error(format!("{} is nested too deeply, does it expand itself?", call.text));
+    }
+    for mut token in tokens.into_iter().rev() {
+      token.depth = call.depth + 1;
+      self.tokens.push_front(token);
+    }
+    Ok(())
+  }
+
+  fn is_register(&self, text: &str) -> bool {
+    self.aliases.contains_key(text) || parse_register(text).is_some()
+  }
+
+  fn register(&mut self) -> AssemblerResult<u16> {
+    let token = self.next()?;
+    match self.aliases.get(&token.text).copied().or_else(|| parse_register(&token.text)) {
+      Some(register) if !token.string => Ok(register as u16),
+      _ => self.error(format!("Expected a register, found {}", token.text)),
+    }
+  }
+
+  // A number, or a name that's already defined
+  fn number(&self, token: &Token) -> Option<i64> {
+    if token.string {
+      return None;
+    }
+    parse_number(&token.text)
+      .or_else(|| self.constants.get(&token.text).map(|&value| value as i64))
+      .or_else(|| self.labels.get(&token.text).map(|&address| address as i64))
+  }
+
+  fn value(&mut self, min: i64, max: i64) -> AssemblerResult<i64> {
+    let token = self.next()?;
+    match self.number(&token) {
+      Some(value) if (min..=max).contains(&value) => Ok(value),
+      Some(value) => self.error(format!("{value} is out of range, expected {min} to {max}")),
+      None => self.error(format!("Undefined name {}", token.text)),
+    }
+  }
+
+  // Anything from -128 to 255, with negative numbers as two's complement
+  fn byte_value(&mut self) -> AssemblerResult<u16> {
+    Ok(self.value(-128, 255)? as u8 as u16)
+  }
+
+  fn nibble_value(&mut self) -> AssemblerResult<u16> {
+    Ok(self.value(0, 15)? as u16)
+  }
+
+  // An address, which can be a label defined further on. Those are filled in
+  // at the current address once they're known.
+  fn address_value(&mut self, patch: Patch) -> AssemblerResult<u16> {
+    let token = self.next()?;
+    let max = match patch {
+      Patch::Address | Patch::Unpack(_) => 0xFFF,
+      Patch::Wide | Patch::UnpackLong => MAX_ADDRESS as i64,
+    };
+    match self.number(&token) {
+      Some(value) if (0..=max).contains(&value) => Ok(value as u16),
+      Some(value) => self.error(format!("Address {value:#X} is out of range")),
+      None if token.string || self.is_register(&token.text) => {
+        self.error(format!("Expected an address, found {}", token.text))
+      }
+      None => {
+        self.forward.entry(token.text).or_default().push((self.pc, patch, self.line));
+        Ok(0)
+      }
+    }
+  }
+
+  fn byte(&mut self, value: u8) -> AssemblerResult {
+    if self.pc > MAX_ADDRESS {
+      return self.error("The program doesn't fit in memory");
+    }
+    let index = self.pc - ROM_OFFSET;
+    if index >= self.rom.len() {
+      self.rom.resize(index + 1, 0);
+    }
+    self.rom[index] = value;
+    self.pc += 1;
+    Ok(())
+  }
+
+  fn instruction(&mut self, opcode: u16) -> AssemblerResult {
+    let address = self.pc;
+    let [high, low] = opcode.to_be_bytes();
+    self.byte(high)?;
+    self.byte(low)?;
+    self.lines.push((address as u16, self.line));
+    Ok(())
+  }
+
+  // Fills an address into code that's already been written
+  fn patch(&mut self, at: usize, patch: Patch, address: usize) -> AssemblerResult {
+    let max = match patch {
+      Patch::Address | Patch::Unpack(_) => 0xFFF,
+      Patch::Wide | Patch::UnpackLong => MAX_ADDRESS,
+    };
+    if address > max {
+      return self.error(format!("Address {address:#X} is out of range"));
+    }
+    let index = at - ROM_OFFSET;
+    let [high, low] = (address as u16).to_be_bytes();
+    match patch {
+      Patch::Address => {
+        self.rom[index] = (self.rom[index] & 0xF0) | high;
+        self.rom[index + 1] = low;
+      }
+      Patch::Wide => {
+        self.rom[index] = high;
+        self.rom[index + 1] = low;
+      }
+      Patch::Unpack(nibble) => {
+        self.rom[index + 1] = (nibble << 4) | high;
+        self.rom[index + 3] = low;
+      }
+      Patch::UnpackLong => {
+        self.rom[index + 1] = high;
+        self.rom[index + 3] = low;
+      }
+    }
+    Ok(())
+  }
+
+  fn define_label(&mut self, name: String, address: usize) -> AssemblerResult {
+    if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+      return self.error(format!("{name} is already defined"));
+    }
+    for (at, patch, _) in self.forward.remove(&name).unwrap_or_default() {
+      self.patch(at, patch, address)?;
+    }
+    self.labels.insert(name, address);
+    Ok(())
+  }
+
+  // Patches a jump that's already been written to go to the address
+  fn resolve_jump(&mut self, at: usize, address: usize) -> AssemblerResult {
+    self.patch(at, Patch::Address, address)
+  }
+
+  fn statement(&mut self, token: Token) -> AssemblerResult {
+    if token.string {
+      return self.error(format!("Unexpected string \"{}\"", token.text));
+    }
+    match token.text.as_str() {
+      ":" => {
+        let name = self.name()?;
+        // A main right at the start doesn't need jumping to, as long as
+        // nothing's been laid out before it that would move
+        let first = self.rom.len() == 2 && self.labels.is_empty() && !self.org;
+        if name == "main" && self.jump_to_main && first {
+          self.jump_to_main = false;
+          self.rom.clear();
+          self.pc = ROM_OFFSET;
+        }
+        self.define_label(name, self.pc)?;
+      }
+      ":next" => {
+        let name = self.name()?;
+        self.define_label(name, self.pc + 1)?;
+      }
+      ":alias" => {
+        let name = self.name()?;
+        let register = if self.peek(0) == Some("{") {
+          let value = self.calculate()?;
+          if !(0.0..16.0).contains(&value) {
+            return self.error(format!("{value} is not a register"));
+          }
+          value as u8
+        } else {
+          self.register()? as u8
+        };
+        self.aliases.insert(name, register);
+      }
+      ":const" => {
+        let name = self.name()?;
+        let value = self.value(i64::MIN, i64::MAX)?;
+        if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+          return self.error(format!("{name} is already defined"));
+        }
+        self.constants.insert(name, value as f64);
+      }
+      ":calc" => {
+        let name = self.name()?;
+        let value = self.calculate()?;
+        if self.labels.contains_key(&name) {
+          return self.error(format!("{name} is already defined"));
+        }
+        self.constants.insert(name, value);
+      }
+      ":byte" => {
+        let value = match self.peek(0) {
+          Some("{") => self.calculate()? as i64 as u8,
+          _ => self.byte_value()? as u8,
+        };
+        self.byte(value)?;
+      }
+      ":pointer" => {
+        let [high, low] = self.address_value(Patch::Wide)?.to_be_bytes();
+        self.byte(high)?;
+        self.byte(low)?;
+      }
+      ":org" => {
+        let address = self.value(ROM_OFFSET as i64, MAX_ADDRESS as i64)?;
+        self.pc = address as usize;
+        self.org = true;
+      }
+      ":unpack" => {
+        let hi = *self.aliases.get("unpack-hi").unwrap_or(&0) as u16;
+        let lo = *self.aliases.get("unpack-lo").unwrap_or(&1) as u16;
+        let (high, low) = if self.peek(0) == Some("long") {
+          self.next()?;
+          let address = self.address_value(Patch::UnpackLong)?;
+          (address >> 8, address & 0xFF)
+        } else {
+          let nibble = self.nibble_value()?;
+          let address = self.address_value(Patch::Unpack(nibble as u8))?;
+          ((nibble << 4) | (address >> 8), address & 0xFF)
+        };
+        self.instruction(0x6000 | (hi << 8) | high)?;
+        self.instruction(0x6000 | (lo << 8) | low)?;
+      }
+      ":macro" => {
+        let name = self.name()?;
+        let mut args = Vec::new();
+        while !matches!(self.peek(0), Some("{") | None) {
+          args.push(self.name()?);
+        }
+        let body = self.block()?;
+        self.macros.insert(name, Macro { args, body, calls: 0 });
+      }
+      ":stringmode" => {
+        let name = self.name()?;
+        let alphabet = self.string()?;
+        let body = self.block()?;
+        let mode = self.string_modes.entry(name).or_default();
+        for (index, c) in alphabet.chars().enumerate() {
+          mode.insert(c, (index, body.clone()));
+        }
+      }
+      ":assert" => {
+        let message = match self.tokens.front() {
+          Some(token) if token.string => Some(self.string()?),
+          _ => None,
+        };
+        if self.calculate()? == 0.0 {
+          return self.error(match message {
+            Some(message) => format!("Assertion failed: {message}"),
+            None => "Assertion failed".into(),
+          });
+        }
+      }
+      ":call" => {
+        let address = self.address_value(Patch::Address)?;
+        self.instruction(0x2000 | address)?;
+      }
+      // Only of use to Octo's debugger
+      ":proto" | ":breakpoint" => {
+        self.next()?;
+      }
+      ":monitor" => {
+        self.next()?;
+        self.next()?;
+      }
+      "return" | ";" => self.instruction(0x00EE)?,
+      "clear" => self.instruction(0x00E0)?,
+      "exit" => self.instruction(0x00FD)?,
+      "lores" => self.instruction(0x00FE)?,
+      "hires" => self.instruction(0x00FF)?,
+      "scroll-down" => {
+        let rows = self.nibble_value()?;
+        self.instruction(0x00C0 | rows)?;
+      }
+      "scroll-up" => {
+        let rows = self.nibble_value()?;
+        self.instruction(0x00D0 | rows)?;
+      }
+      "scroll-right" => self.instruction(0x00FB)?,
+      "scroll-left" => self.instruction(0x00FC)?,
+      "jump" => {
+        let address = self.address_value(Patch::Address)?;
+        self.instruction(0x1000 | address)?;
+      }
+      "jump0" => {
+        let address = self.address_value(Patch::Address)?;
+        self.instruction(0xB000 | address)?;
+      }
+      "native" => {
+        let address = self.address_value(Patch::Address)?;
+        self.instruction(address)?;
+      }
+      "sprite" => {
+        let x = self.register()?;
+        let y = self.register()?;
+        let height = self.nibble_value()?;
+        self.instruction(0xD000 | (x << 8) | (y << 4) | height)?;
+      }
+      "bcd" => {
+        let x = self.register()?;
+        self.instruction(0xF033 | (x << 8))?;
+      }
+      "delay" | "buzzer" | "pitch" => {
+        self.expect(":=")?;
+        let x = self.register()?;
+        let low = match token.text.as_str() {
+          "delay" => 0x15,
+          "buzzer" => 0x18,
+          _ => 0x3A,
+        };
+        self.instruction(0xF000 | (x << 8) | low)?;
+      }
+      "plane" => {
+        let mask = self.nibble_value()?;
+        self.instruction(0xF001 | (mask << 8))?;
+      }
+      "audio" => self.instruction(0xF002)?,
+      "saveflags" => {
+        let x = self.register()?;
+        self.instruction(0xF075 | (x << 8))?;
+      }
+      "loadflags" => {
+        let x = self.register()?;
+        self.instruction(0xF085 | (x << 8))?;
+      }
+      "save" | "load" => {
+        let x = self.register()?;
+        let save = token.text == "save";
+        if self.peek(0) == Some("-") {
+          self.next()?;
+          let y = self.register()?;
+          self.instruction(0x5000 | (x << 8) | (y << 4) | if save { 2 } else { 3 })?;
+        } else {
+          self.instruction(0xF000 | (x << 8) | if save { 0x55 } else { 0x65 })?;
+        }
+      }
+      "i" => self.index()?,
+      "if" => {
+        let index = if matches!(self.peek(1), Some("key" | "-key")) { 2 } else { 3 };
+        match self.peek(index) {
+          Some("then") => {
+            self.conditional(false)?;
+            self.expect("then")?;
+          }
+          Some("begin") => {
+            self.conditional(true)?;
+            self.expect("begin")?;
+            self.branches.push((self.pc, self.line));
+            self.instruction(0x1000)?;
+          }
+          _ => return self.error("Expected then or begin after the if"),
+        }
+      }
+      "else" => {
+        let Some((jump, _)) = self.branches.pop() else {
+          return self.error("This else doesn't have a begin");
+        };
+        self.resolve_jump(jump, self.pc + 2)?;
+        self.branches.push((self.pc, self.line));
+        self.instruction(0x1000)?;
+      }
+      "end" => {
+        let Some((jump, _)) = self.branches.pop() else {
+          return self.error("This end doesn't have a begin");
+        };
+        self.resolve_jump(jump, self.pc)?;
+      }
+      "loop" => self.loops.push(Loop { start: self.pc, exits: Vec::new(), line: self.line }),
+      "while" => {
+        if self.loops.is_empty() {
+          return self.error("This while isn't inside a loop");
+        }
+        self.conditional(true)?;
+        let exit = self.pc;
+        self.loops.last_mut().unwrap().exits.push(exit);
+        self.instruction(0x1000)?;
+      }
+      "again" => {
+        let Some(open) = self.loops.pop() else {
+          return self.error("This again doesn't have a loop");
+        };
+        self.instruction(0x1000)?;
+        self.resolve_jump(self.pc - 2, open.start)?;
+        for exit in open.exits {
+          self.resolve_jump(exit, self.pc)?;
+        }
+      }
+      "then" | "begin" | "key" | "-key" | "{" | "}" => {
+        return self.error(format!("Unexpected {}", token.text));
+      }
+      text if parse_number(text).is_some() => {
+        self.tokens.push_front(token);
+        let value = self.byte_value()?;
+        self.byte(value as u8)?;
+      }
+      text if self.is_register(text) => {
+        self.tokens.push_front(token);
+        self.assignment()?;
+      }
+      text if self.macros.contains_key(text) => self.expand_macro(token)?,
+      text if self.string_modes.contains_key(text) => self.expand_string(token)?,
+      // Anything else names a subroutine, perhaps one further on
+      _ => {
+        self.tokens.push_front(token);
+        let address = self.address_value(Patch::Address)?;
+        self.instruction(0x2000 | address)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn index(&mut self) -> AssemblerResult {
+    let op = self.next()?;
+    if op.is("+=") {
+      let x = self.register()?;
+      return self.instruction(0xF01E | (x << 8));
+    }
+    if !op.is(":=") {
+      return self.error(format!("Expected := or += after i, found {}", op.text));
+    }
+    match self.peek(0) {
+      Some("hex") => {
+        self.next()?;
+        let x = self.register()?;
+        self.instruction(0xF029 | (x << 8))
+      }
+      Some("bighex") => {
+        self.next()?;
+        let x = self.register()?;
+        self.instruction(0xF030 | (x << 8))
+      }
+      Some("long") => {
+        self.next()?;
+        self.instruction(0xF000)?;
+        // The address is the rest of the same instruction
+        let [high, low] = self.address_value(Patch::Wide)?.to_be_bytes();
+        self.byte(high)?;
+        self.byte(low)
+      }
+      _ => {
+        let address = self.address_value(Patch::Address)?;
+        self.instruction(0xA000 | address)
+      }
+    }
+  }
+
+  fn assignment(&mut self) -> AssemblerResult {
+    let x = self.register()? << 8;
+    let op = self.next()?;
+    let register = self.peek(0).is_some_and(|text| self.is_register(text));
+    let opcode = match (op.text.as_str(), register) {
+      (":=", true) => 0x8000 | x | self.register()? << 4,
+      (":=", false) => match self.peek(0) {
+        Some("random") => {
+          self.next()?;
+          0xC000 | x | self.byte_value()?
+        }
+        Some("key") => {
+          self.next()?;
+          0xF00A | x
+        }
+        Some("delay") => {
+          self.next()?;
+          0xF007 | x
+        }
+        _ => 0x6000 | x | self.byte_value()?,
+      },
+      ("+=", false) => 0x7000 | x | self.byte_value()?,
+      // There's no subtracting a constant, so it's added negated
+      ("-=", false) => 0x7000 | x | (self.byte_value()? as u8).wrapping_neg() as u16,
+      (op, true) => {
+        let low = match op {
+          "|=" => 0x1,
+          "&=" => 0x2,
+          "^=" => 0x3,
+          "+=" => 0x4,
+          "-=" => 0x5,
+          ">>=" => 0x6,
+          "=-" => 0x7,
+          "<<=" => 0xE,
+          _ => return self.error(format!("Unknown operator {op}")),
+        };
+        0x8000 | x | self.register()? << 4 | low
+      }
+      (op, false) => {
+        return self.error(format!("Expected a register after {op}"));
+      }
+    };
+    self.instruction(opcode)
+  }
+
+  // Compiles the condition of an if or while to the instructions that skip
+  // whatever comes next when it doesn't hold, or when it does if negated
+  fn conditional(&mut self, negated: bool) -> AssemblerResult {
+    let x = self.register()? << 8;
+    let op = self.next()?;
+    let op = match (op.text.as_str(), negated) {
+      ("==", true) => "!=",
+      ("!=", true) => "==",
+      ("key", true) => "-key",
+      ("-key", true) => "key",
+      ("<", true) => ">=",
+      (">", true) => "<=",
+      (">=", true) => "<",
+      ("<=", true) => ">",
+      ("==" | "!=" | "key" | "-key" | "<" | ">" | ">=" | "<=", _) => op.text.as_str(),
+      (op, _) => return self.error(format!("Unknown comparison {op}")),
+    }
+    .to_string();
+    let register = self.peek(0).is_some_and(|text| self.is_register(text));
+    let skip = match op.as_str() {
+      "==" if register => 0x9000 | x | self.register()? << 4,
+      "==" => 0x4000 | x | self.byte_value()?,
+      "!=" if register => 0x5000 | x | self.register()? << 4,
+      "!=" => 0x3000 | x | self.byte_value()?,
+      "key" => 0xE0A1 | x,
+      "-key" => 0xE09E | x,
+      // The rest compare by subtracting in a scratch register and checking
+      // the borrow flag
+      _ => {
+        let temp = *self.aliases.get("compare-temp").unwrap_or(&0xF) as u16;
+        let load = match register {
+          true => 0x8000 | temp << 8 | self.register()? << 4,
+          false => 0x6000 | temp << 8 | self.byte_value()?,
+        };
+        self.instruction(load)?;
+        let (subtract, skip) = match op.as_str() {
+          ">" => (0x5, 0x4F00),
+          "<" => (0x7, 0x4F00),
+          ">=" => (0x7, 0x3F00),
+          _ => (0x5, 0x3F00),
+        };
+        self.instruction(0x8000 | temp << 8 | x >> 4 | subtract)?;
+        skip
+      }
+    };
+    self.instruction(skip)
+  }
+
+  fn expand_macro(&mut self, call: Token) -> AssemblerResult {
+    let count = self.macros[&call.text].args.len();
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+      args.push(self.next()?);
+    }
+    let definition = self.macros.get_mut(&call.text).unwrap();
+    let calls = definition.calls;
+    definition.calls += 1;
+    let body = definition
+      .body
+      .iter()
+      .map(|token| {
+        let mut token = match definition.args.iter().position(|arg| token.is(arg)) {
+          Some(index) => args[index].clone(),
+          None if token.is("CALLS") => Token::word(calls.to_string(), call.line),
+          None => token.clone(),
+        };
+        token.line = call.line;
+        token
+      })
+      .collect();
+    self.expand(&call, body)
+  }
+
+  fn expand_string(&mut self, call: Token) -> AssemblerResult {
+    let text = self.string()?;
+    let mode = &self.string_modes[&call.text];
+    let mut body = Vec::new();
+    for (index, c) in text.chars().enumerate() {
+      let Some((value, tokens)) = mode.get(&c) else {
+        return self.error(format!("String mode {} can't encode {c:?}", call.text));
+      };
+      body.extend(tokens.iter().map(|token| {
+        let value = match token.text.as_str() {
+          _ if token.string => None,
+          "VALUE" => Some(*value),
+          "CHAR" => Some(c as usize),
+          "INDEX" => Some(index),
+          _ => None,
+        };
+        match value {
+          Some(value) => Token::word(value.to_string(), call.line),
+          None => Token { line: call.line, ..token.clone() },
+        }
+      }));
+    }
+    self.expand(&call, body)
+  }
+
+  // Evaluates a { } expression
+  fn calculate(&mut self) -> AssemblerResult<f64> {
+    let tokens = self.block()?;
+    let mut calc = Calc { assembler: self, tokens: &tokens, position: 0 };
+    let value = calc.expression()?;
+    match tokens.get(calc.position) {
+      Some(token) => self.error(format!("Unexpected {} in expression", token.text)),
+      None => Ok(value),
+    }
+  }
+}
+
+// Octo's expressions have no operator precedence: a binary operator takes
+// everything to its right, as does a unary one, so 2 * 3 + 1 is 8. Values are
+// floating point, and bitwise operators work on them as 32-bit integers.
+struct Calc<'a> {
+  assembler: &'a Assembler,
+  tokens: &'a [Token],
+  position: usize,
+}
+
+impl Calc<'_> {
+  fn next(&mut self) -> AssemblerResult<&Token> {
+    let token = self.tokens.get(self.position);
+    self.position += 1;
+    match token {
+      Some(token) => Ok(token),
+      None => self.assembler.error("Unexpected end of expression"),
+    }
+  }
+
+  fn peek(&self) -> Option<&str> {
+    self.tokens.get(self.position).map(|token| token.text.as_str())
+  }
+
+  fn expression(&mut self) -> AssemblerResult<f64> {
+    let unary: Option<fn(f64) -> f64> = match self.peek() {
+      Some("-") => Some(|x| -x),
+      Some("~") => Some(|x| !(x as i64 as i32) as f64),
+      Some("!") => Some(|x| if x == 0.0 { 1.0 } else { 0.0 }),
+      Some("abs") => Some(|x| if x < 0.0 { -x } else { x }),
+      Some("sign") => Some(|x| if x > 0.0 { 1.0 } else if x < 0.0 { -1.0 } else { 0.0 }),
+      Some("floor") => Some(floor),
+      Some("ceil") => Some(|x| -floor(-x)),
+      _ => None,
+    };
+    if let Some(unary) = unary {
+      self.position += 1;
+      return Ok(unary(self.expression()?));
+    }
+    if self.peek() == Some("@") {
+      self.position += 1;
+      let address = self.expression()? as i64;
+      let byte = (address - ROM_OFFSET as i64).try_into().ok().and_then(|index: usize| self.assembler.rom.get(index));
+      return Ok(byte.copied().unwrap_or(0) as f64);
+    }
+
+    let left = self.terminal()?;
+    let binary: fn(f64, f64) -> f64 = match self.peek() {
+      Some("+") => |a, b| a + b,
+      Some("-") => |a, b| a - b,
+      Some("*") => |a, b| a * b,
+      Some("/") => |a, b| a / b,
+      Some("%") => |a, b| a % b,
+      Some("&") => |a, b| (int(a) & int(b)) as f64,
+      Some("|") => |a, b| (int(a) | int(b)) as f64,
+      Some("^") => |a, b| (int(a) ^ int(b)) as f64,
+      Some("<<") => |a, b| int(a).wrapping_shl(int(b) as u32) as f64,
+      Some(">>") => |a, b| int(a).wrapping_shr(int(b) as u32) as f64,
+      Some("min") => |a, b| if b < a { b } else { a },
+      Some("max") => |a, b| if b > a { b } else { a },
+      Some("<") => |a, b| (a < b) as u8 as f64,
+      Some("<=") => |a, b| (a <= b) as u8 as f64,
+      Some("==") => |a, b| (a == b) as u8 as f64,
+      Some("!=") => |a, b| (a != b) as u8 as f64,
+      Some(">=") => |a, b| (a >= b) as u8 as f64,
+      Some(">") => |a, b| (a > b) as u8 as f64,
+      _ => return Ok(left),
+    };
+    self.position += 1;
+    Ok(binary(left, self.expression()?))
+  }
+
+  fn terminal(&mut self) -> AssemblerResult<f64> {
+    let assembler = self.assembler;
+    let token = self.next()?;
+    if token.is("(") {
+      let value = self.expression()?;
+      return match self.next()? {
+        token if token.is(")") => Ok(value),
+        token => assembler.error(format!("Expected ), found {}", token.text)),
+      };
+    }
+    let text = token.text.as_str();
+    let value = match text {
+      _ if token.string => None,
+      "PI" => Some(core::f64::consts::PI),
+      "E" => Some(core::f64::consts::E),
+      "HERE" => Some(assembler.pc as f64),
+      _ => parse_number(text)
+        .map(|value| value as f64)
+        .or_else(|| assembler.constants.get(text).copied())
+        .or_else(|| assembler.labels.get(text).map(|&address| address as f64))
+        .or_else(|| assembler.aliases.get(text).or(parse_register(text).as_ref()).map(|&register| register as f64)),
+    };
+    match value {
+      Some(value) => Ok(value),
+      None if assembler.forward.contains_key(text) => {
+        assembler.error(format!("{text} isn't defined yet, so can't be used in an expression"))
+      }
+      None => assembler.error(format!("Undefined name {text} in expression")),
+    }
+  }
+}
+
+// Truncates to an integer the way JavaScript's bitwise operators do
+fn int(value: f64) -> i32 {
+  value as i64 as i32
+}
+
+// f64::floor needs std
+fn floor(value: f64) -> f64 {
+  let truncated = value as i64 as f64;
+  if truncated > value {
+    truncated - 1.0
+  } else {
+    truncated
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assemble_ok(source: &str) -> Vec<u8> {
+    assemble(source).unwrap_or_else(|err| panic!("{err}"))
+  }
+
+  #[test]
+  fn test_main() {
+    // main first needs no jump
+    assert_eq!(assemble_ok(": main clear"), [0x00, 0xE0]);
+    assert_eq!(assemble_ok(": sub return : main sub"), [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02]);
+    assert_eq!(assemble("clear").unwrap_err().message, "The program is missing a main label");
+    // Data before main keeps the jump, even when :org puts main where it would go
+    assert_eq!(
+      assemble_ok(": data 1 2 3 :org 0x202 : main clear jump main"),
+      [0x12, 0x02, 0x00, 0xE0, 0x12, 0x02]
+    );
+    assert_eq!(assemble_ok(":org 0x202 : main clear"), [0x12, 0x02, 0x00, 0xE0]);
+    assert_eq!(assemble_ok(": start : main jump start"), [0x12, 0x02, 0x12, 0x02]);
+  }
+
+  #[test]
+  fn test_symbols() {
+    let source = "
+      : main
+        v0 := 1  # a comment
+
+        sub
+        i := long data
+      : sub
+        :pointer data
+        return
+      : data
+    ";
+    let (rom, symbols) = assemble_with_symbols(source).unwrap();
+    assert_eq!(rom, assemble_ok(source));
+    assert_eq!(symbols.lines, [(0x200, 3), (0x202, 5), (0x204, 6), (0x20A, 9)]);
+    assert_eq!(symbols.address(3), Some((0x200, 3)));
+    assert_eq!(symbols.address(4), Some((0x202, 5)));
+    assert_eq!(symbols.address(10), None);
+    assert_eq!(symbols.line(0x204), Some(6));
+    assert_eq!(symbols.line(0x206), None);
+    assert_eq!(symbols.label(0x20A), Some("sub"));
+    assert_eq!(symbols.label(0x1FF), None);
+  }
+
+  #[test]
+  fn test_instructions() {
+    let source = "
+      : main
+        v3 := 0x2A  v3 += 1  v3 -= 1  v3 := v4  v3 <<= v4  v3 =- vF
+        v0 := random 0xFF  v0 := key  v0 := delay  delay := v1  buzzer := v1
+        i := main  i += v2  i := hex v5  bcd v6  save v7  load v7
+        sprite v1 v2 5  jump0 0x300  ;
+    ";
+    assert_eq!(
+      assemble_ok(source),
+      [
+        0x63, 0x2A, 0x73, 0x01, 0x73, 0xFF, 0x83, 0x40, 0x83, 0x4E, 0x83, 0xF7, //
+        0xC0, 0xFF, 0xF0, 0x0A, 0xF0, 0x07, 0xF1, 0x15, 0xF1, 0x18, //
+        0xA2, 0x00, 0xF2, 0x1E, 0xF5, 0x29, 0xF6, 0x33, 0xF7, 0x55, 0xF7, 0x65, //
+        0xD1, 0x25, 0xB3, 0x00, 0x00, 0xEE,
+      ]
+    );
+    let source = ": main hires plane 3 i := long data save v1 - v2 audio pitch := v0 : data";
+    assert_eq!(
+      assemble_ok(source),
+      [0x00, 0xFF, 0xF3, 0x01, 0xF0, 0x00, 0x02, 0x0E, 0x51, 0x22, 0xF0, 0x02, 0xF0, 0x3A]
+    );
+  }
+
+  #[test]
+  fn test_control_flow() {
+    let source = "
+      : main
+        if v0 == 1 then v1 := 2
+        if v0 > v2 then return
+        if v0 key begin clear else return end
+        loop
+          v0 += 1
+          while v0 != 8
+        again
+    ";
+    assert_eq!(
+      assemble_ok(source),
+      [
+        0x40, 0x01, 0x61, 0x02, // if v0 == 1 then
+        0x8F, 0x20, 0x8F, 0x05, 0x4F, 0x00, 0x00, 0xEE, // if v0 > v2 then
+        0xE0, 0x9E, 0x12, 0x14, 0x00, 0xE0, 0x12, 0x16, 0x00, 0xEE, // if .. else .. end
+        0x70, 0x01, 0x40, 0x08, 0x12, 0x1E, 0x12, 0x16, // loop .. while .. again
+      ]
+    );
+    assert!(assemble(": main loop").is_err());
+    assert!(assemble(": main end").is_err());
+  }
+
+  #[test]
+  fn test_directives() {
+    let source = "
+      :const SIZE 3
+      :alias counter v4
+      :calc NINE { SIZE * 2 + 1 }
+      :macro set REG VALUE { REG := VALUE }
+      :stringmode text \"ABC\" { :byte { VALUE + 0x41 } }
+      : main
+        set counter NINE
+        :unpack 0xA data
+        text \"CAB\"
+      :org 0x210
+      : data
+        :pointer main
+        :byte { HERE }
+    ";
+    assert_eq!(
+      assemble_ok(source),
+      [
+        0x64, 0x09, 0x60, 0xA2, 0x61, 0x10, 0x43, 0x41, 0x42, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x02, 0x00, 0x12,
+      ]
+    );
+  }
+
+  #[test]
+  fn test_errors() {
+    let error = assemble(": main\n  jump nowhere").unwrap_err();
+    assert_eq!(error, AssemblerError { line: 2, message: "Undefined name nowhere".into() });
+    assert_eq!(assemble(": main\nv0 := 256").unwrap_err().line, 2);
+    assert!(assemble(": main : main").is_err());
+    assert!(assemble(": main :calc X { later } : later").is_err());
+    assert!(assemble(": main \"unterminated").is_err());
+    let error = assemble(":macro m { m }\n: main m").unwrap_err();
+    assert_eq!(error, AssemblerError { line: 2, message: "m is nested too deeply, does it expand itself?".into() });
+    assert!(assemble(":macro a { b } :macro b { a } : main a").is_err());
+  }
+}
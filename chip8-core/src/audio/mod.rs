@@ -0,0 +1,100 @@
+pub mod wav;
+
+use crate::Chip8;
+
+const AMPLITUDE: f32 = 0.25;
+// Rate the pattern buffer is played back at when the pitch register is 64
+const BASE_PLAYBACK_RATE: f32 = 4000.0;
+const PATTERN_BITS: f32 = 128.0;
+
+// Everything a sink needs to know to produce a frame's worth of sound
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sound {
+  pub active: bool,
+  pub pattern: [u8; 16],
+  pub pitch: u8,
+}
+
+impl Default for Sound {
+  fn default() -> Self {
+    Self {
+      active: false,
+      pattern: [0; 16],
+      pitch: 64,
+    }
+  }
+}
+
+impl Sound {
+  pub fn from_chip8(chip8: &Chip8) -> Self {
+    Self {
+      active: chip8.sound_active(),
+      pattern: *chip8.audio_pattern(),
+      pitch: chip8.pitch(),
+    }
+  }
+
+  // Bits of the pattern buffer played per second
+  pub fn playback_rate(&self) -> f32 {
+    BASE_PLAYBACK_RATE * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+  }
+}
+
+pub trait AudioSink {
+  // Called once per 60 Hz frame with the sound state at the end of that frame
+  fn play(&mut self, sound: &Sound);
+}
+
+// Steps through the 128 bit pattern buffer, one sample at a time
+pub struct Oscillator {
+  sample_rate: u32,
+  position: f32,
+}
+
+impl Oscillator {
+  pub fn new(sample_rate: u32) -> Self {
+    Self {
+      sample_rate,
+      position: 0.0,
+    }
+  }
+
+  pub fn next_sample(&mut self, sound: &Sound) -> f32 {
+    if !sound.active {
+      self.position = 0.0;
+      return 0.0;
+    }
+    let bit = self.position as usize;
+    let high = sound.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+    self.position += sound.playback_rate() / self.sample_rate as f32;
+    self.position %= PATTERN_BITS;
+    if high { AMPLITUDE } else { -AMPLITUDE }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_playback_rate() {
+    let mut sound = Sound::default();
+    assert_eq!(sound.playback_rate(), 4000.0);
+    sound.pitch = 112;
+    assert_eq!(sound.playback_rate(), 8000.0);
+  }
+
+  #[test]
+  fn test_oscillator() {
+    let mut oscillator = Oscillator::new(4000);
+    let mut sound = Sound {
+      active: true,
+      pattern: [0xF0; 16],
+      pitch: 64,
+    };
+    let samples: Vec<f32> = (0..8).map(|_| oscillator.next_sample(&sound)).collect();
+    assert_eq!(samples, [AMPLITUDE, AMPLITUDE, AMPLITUDE, AMPLITUDE, -AMPLITUDE, -AMPLITUDE, -AMPLITUDE, -AMPLITUDE]);
+    sound.active = false;
+    assert_eq!(oscillator.next_sample(&sound), 0.0);
+  }
+}
@@ -0,0 +1,103 @@
+use super::{AudioSink, Oscillator, Sound};
+use crate::FRAMES_PER_SECOND;
+use std::io::{self, Write};
+
+const BITS_PER_SAMPLE: u16 = 16;
+
+// Renders sound into memory instead of playing it, so it can be checked or
+// written out as a mono 16 bit WAV file
+pub struct WavSink {
+  sample_rate: u32,
+  oscillator: Oscillator,
+  samples: Vec<i16>,
+}
+
+impl WavSink {
+  pub fn new(sample_rate: u32) -> Self {
+    Self {
+      sample_rate,
+      oscillator: Oscillator::new(sample_rate),
+      samples: Vec::new(),
+    }
+  }
+
+  pub fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  pub fn samples(&self) -> &[i16] {
+    &self.samples
+  }
+
+  pub fn write_wav<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+    let block_align = BITS_PER_SAMPLE / 8;
+    let data_len = (self.samples.len() * block_align as usize) as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // PCM, mono
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&self.sample_rate.to_le_bytes())?;
+    writer.write_all(&(self.sample_rate * block_align as u32).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    for sample in &self.samples {
+      writer.write_all(&sample.to_le_bytes())?;
+    }
+    Ok(())
+  }
+}
+
+impl AudioSink for WavSink {
+  fn play(&mut self, sound: &Sound) {
+    let samples_per_frame = self.sample_rate as usize / FRAMES_PER_SECOND as usize;
+    for _ in 0..samples_per_frame {
+      let sample = self.oscillator.next_sample(sound);
+      self.samples.push((sample * i16::MAX as f32) as i16);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Chip8;
+
+  #[test]
+  fn test_sound_timer() {
+    // LD V0, 0x03; LD ST, V0; JP 0x204
+    let mut chip8 = Chip8::default();
+    chip8.load_rom(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+    let mut sink = WavSink::new(48000);
+    for _ in 0..6 {
+      chip8.run_frame().unwrap();
+      sink.play(&Sound::from_chip8(&chip8));
+    }
+    // The timer is set during the first frame and ticks at the start of the
+    // next three, so the beep lasts three frames
+    let samples = sink.samples();
+    assert_eq!(samples.len(), 6 * 800);
+    assert!(samples[..3 * 800].iter().any(|&sample| sample != 0));
+    assert!(samples[3 * 800..].iter().all(|&sample| sample == 0));
+  }
+
+  #[test]
+  fn test_write_wav() {
+    let mut sink = WavSink::new(8000);
+    sink.play(&Sound { active: true, ..Sound::default() });
+    let mut wav = Vec::new();
+    sink.write_wav(&mut wav).unwrap();
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(&wav[8..12], b"WAVE");
+    assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 8000);
+    assert_eq!(wav.len(), 44 + 133 * 2);
+  }
+}
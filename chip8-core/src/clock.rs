@@ -0,0 +1,44 @@
+use core::time::Duration;
+
+pub const FRAMES_PER_SECOND: u64 = 60;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+// Turns elapsed wall clock time into whole 60 Hz frames
+#[derive(Default)]
+pub struct FrameClock {
+  // Elapsed time scaled by FRAMES_PER_SECOND, so one frame is exactly one
+  // second worth of nanoseconds and no rounding error builds up between updates.
+  elapsed: u64,
+}
+
+impl FrameClock {
+  // Advances the clock by delta and returns how many frames have passed
+  pub fn update(&mut self, delta: &Duration) -> usize {
+    self.elapsed += delta.as_nanos() as u64 * FRAMES_PER_SECOND;
+    let frames = self.elapsed / NANOS_PER_SECOND;
+    self.elapsed %= NANOS_PER_SECOND;
+    frames as usize
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_update() {
+    let mut clock = FrameClock::default();
+    assert_eq!(clock.update(&Duration::from_millis(10)), 0);
+    assert_eq!(clock.update(&Duration::from_millis(10)), 1);
+    assert_eq!(clock.update(&Duration::from_secs(1)), 60);
+  }
+
+  #[test]
+  fn test_update_accumulates() {
+    let mut clock = FrameClock::default();
+    let frames: usize = (0..10)
+      .map(|_| clock.update(&Duration::from_millis(5)))
+      .sum();
+    assert_eq!(frames, 3);
+  }
+}
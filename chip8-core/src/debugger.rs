@@ -0,0 +1,530 @@
+use super::{
+  error::InterpreterError,
+  memory::{Access, WatchHit},
+  rng::RandomSource,
+  Chip8,
+};
+use alloc::{format, string::String, vec::Vec};
+use core::{fmt, str::FromStr, time::Duration};
+
+// Runs a machine under the control of breakpoints and stepping commands.
+// Watchpoints are set on the machine's `Memory`, and the debugger stops the
+// machine once an instruction trips one.
+//
+// Whatever the command, the machine only runs from `update` or `run_frame`,
+// so a frontend keeps calling those every frame and they do nothing while
+// paused. A stop pauses the debugger and says why.
+#[derive(Default)]
+pub struct Debugger {
+  breakpoints: Vec<Breakpoint>,
+  mode: Mode,
+  // Set when the machine starts again after a stop, so a breakpoint on the
+  // instruction it stopped at doesn't fire straight away
+  resuming: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Mode {
+  #[default]
+  Running,
+  Paused,
+  // Stop after the next instruction
+  Step,
+  // Stop once the stack is back to `depth` with the PC at `address`, after
+  // the CALL there returns
+  StepOver { address: u16, depth: usize },
+  // Stop once the stack is shallower than `depth`
+  StepOut { depth: usize },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+  pub address: u16,
+  // Only stop when this holds, e.g. V3 == 0x10
+  pub condition: Option<Condition>,
+}
+
+// Why the debugger paused the machine
+#[derive(Debug)]
+pub enum Stop {
+  // A step, step over or step out finished
+  Step,
+  Breakpoint(u16),
+  // The instruction at `pc` touched watched memory
+  Watchpoint { pc: u16, hit: WatchHit },
+  Exited,
+  // An instruction failed, leaving the PC on it
+  Fault(InterpreterError),
+}
+
+impl Debugger {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Replaces any breakpoint already at the address
+  pub fn add_breakpoint(&mut self, address: u16, condition: Option<Condition>) {
+    self.remove_breakpoint(address);
+    self.breakpoints.push(Breakpoint { address, condition });
+  }
+
+  pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+    let count = self.breakpoints.len();
+    self.breakpoints.retain(|breakpoint| breakpoint.address != address);
+    self.breakpoints.len() != count
+  }
+
+  pub fn clear_breakpoints(&mut self) {
+    self.breakpoints.clear();
+  }
+
+  pub fn breakpoints(&self) -> &[Breakpoint] {
+    &self.breakpoints
+  }
+
+  pub fn is_paused(&self) -> bool {
+    self.mode == Mode::Paused
+  }
+
+  pub fn pause(&mut self) {
+    self.mode = Mode::Paused;
+  }
+
+  pub fn resume(&mut self) {
+    self.start(Mode::Running);
+  }
+
+  pub fn toggle_pause(&mut self) {
+    if self.is_paused() {
+      self.resume();
+    } else {
+      self.pause();
+    }
+  }
+
+  pub fn step(&mut self) {
+    self.start(Mode::Step);
+  }
+
+  // Steps, except that a CALL runs until the subroutine returns
+  pub fn step_over<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(&mut self, chip8: &Chip8<R, MEMORY, PIXELS>) {
+    let pc = chip8.pc();
+    let call = chip8.memory.peek(pc as usize, 1).is_ok_and(|bytes| bytes[0] >> 4 == 0x2);
+    match call {
+      true => self.start(Mode::StepOver { address: pc.wrapping_add(2), depth: chip8.stack().len() }),
+      false => self.step(),
+    }
+  }
+
+  // Runs until the current subroutine returns. There's nothing to return from
+  // outside of one, so there it just steps.
+  pub fn step_out<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(&mut self, chip8: &Chip8<R, MEMORY, PIXELS>) {
+    match chip8.stack().len() {
+      0 => self.step(),
+      depth => self.start(Mode::StepOut { depth }),
+    }
+  }
+
+  fn start(&mut self, mode: Mode) {
+    self.resuming = true;
+    self.mode = mode;
+  }
+
+  // Like `Chip8::update`, but stops the machine when the debugger says to
+  pub fn update<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(
+    &mut self,
+    chip8: &mut Chip8<R, MEMORY, PIXELS>,
+    delta: &Duration,
+  ) -> Option<Stop> {
+    self.update_frames(chip8, delta, |_| {})
+  }
+
+  // Like `update`, calling `on_frame` after each frame that runs to its end,
+  // e.g. to hand the frame's sound to an `AudioSink`
+  pub fn update_frames<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(
+    &mut self,
+    chip8: &mut Chip8<R, MEMORY, PIXELS>,
+    delta: &Duration,
+    mut on_frame: impl FnMut(&Chip8<R, MEMORY, PIXELS>),
+  ) -> Option<Stop> {
+    if self.is_paused() {
+      return None;
+    }
+    let frames = chip8.clock.update(delta);
+    (0..frames).find_map(|_| {
+      let stop = self.run_frame(chip8);
+      if stop.is_none() {
+        on_frame(chip8);
+      }
+      stop
+    })
+  }
+
+  // Like `Chip8::run_frame`, but stops partway through the frame when the
+  // debugger says to. The next call carries on with the rest of it.
+  pub fn run_frame<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(&mut self, chip8: &mut Chip8<R, MEMORY, PIXELS>) -> Option<Stop> {
+    if self.is_paused() {
+      return None;
+    }
+    loop {
+      let pc = chip8.pc();
+      // Idle cycles don't run the instruction at the PC, so don't stop on it
+      let idle = chip8.exited() || (chip8.registers.display_wait && chip8.frame_cycle != 0);
+      if !idle && !self.resuming && self.breakpoint_hit(chip8) {
+        return self.stop(Stop::Breakpoint(pc));
+      }
+
+      let executed = match chip8.step() {
+        Ok(executed) => executed,
+        Err(err) => return self.stop(Stop::Fault(err)),
+      };
+      if executed {
+        self.resuming = false;
+      }
+      if let Some(hit) = chip8.memory.take_watch_hit() {
+        return self.stop(Stop::Watchpoint { pc, hit });
+      }
+      if chip8.exited() {
+        return self.stop(Stop::Exited);
+      }
+      let depth = chip8.stack().len();
+      let done = match self.mode {
+        Mode::Step => executed,
+        Mode::StepOver { address, depth: call_depth } => chip8.pc() == address && depth <= call_depth,
+        Mode::StepOut { depth: call_depth } => depth < call_depth,
+        Mode::Running | Mode::Paused => false,
+      };
+      if done {
+        return self.stop(Stop::Step);
+      }
+      if chip8.frame_cycle == 0 {
+        return None;
+      }
+    }
+  }
+
+  fn breakpoint_hit<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(&self, chip8: &Chip8<R, MEMORY, PIXELS>) -> bool {
+    let pc = chip8.pc();
+    self.breakpoints.iter().any(|breakpoint| {
+      breakpoint.address == pc && breakpoint.condition.as_ref().is_none_or(|condition| condition.holds(chip8))
+    })
+  }
+
+  fn stop(&mut self, stop: Stop) -> Option<Stop> {
+    self.mode = Mode::Paused;
+    Some(stop)
+  }
+}
+
+impl fmt::Display for Stop {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Step => write!(f, "Stepped"),
+      Self::Breakpoint(address) => write!(f, "Breakpoint at {address:#06x}"),
+      Self::Watchpoint { pc, hit } => {
+        let access = match hit.access {
+          Access::Write => "written",
+          _ => "read",
+        };
+        write!(f, "Watched address {:#06x} {access} by the instruction at {pc:#06x}", hit.address)
+      }
+      Self::Exited => write!(f, "The program exited"),
+      Self::Fault(err) => write!(f, "{err}"),
+    }
+  }
+}
+
+// A comparison of two machine values, written like V3 == 0x10 or [I] != 0.
+// Operands are V0 to VF, I, PC, SP, DT, ST, numbers in decimal or 0x hex, or
+// the byte at an address in brackets, like [I] or [0x300].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+  left: Operand,
+  comparison: Comparison,
+  right: Operand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+  V(usize),
+  I,
+  Pc,
+  Sp,
+  Delay,
+  Sound,
+  Value(u16),
+  ByteAtI,
+  ByteAt(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+  Equal,
+  NotEqual,
+  Less,
+  LessOrEqual,
+  Greater,
+  GreaterOrEqual,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConditionError(String);
+
+impl fmt::Display for ConditionError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Invalid condition: {}", self.0)
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConditionError {}
+
+impl Condition {
+  pub fn holds<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(&self, chip8: &Chip8<R, MEMORY, PIXELS>) -> bool {
+    let (left, right) = (self.left.value(chip8), self.right.value(chip8));
+    match self.comparison {
+      Comparison::Equal => left == right,
+      Comparison::NotEqual => left != right,
+      Comparison::Less => left < right,
+      Comparison::LessOrEqual => left <= right,
+      Comparison::Greater => left > right,
+      Comparison::GreaterOrEqual => left >= right,
+    }
+  }
+}
+
+impl Operand {
+  fn value<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(self, chip8: &Chip8<R, MEMORY, PIXELS>) -> u16 {
+    let byte_at = |address: u16| chip8.memory.peek(address as usize, 1).map_or(0, |bytes| bytes[0] as u16);
+    match self {
+      Self::V(index) => chip8.v()[index] as u16,
+      Self::I => chip8.i(),
+      Self::Pc => chip8.pc(),
+      Self::Sp => chip8.stack().len() as u16,
+      Self::Delay => chip8.delay_timer() as u16,
+      Self::Sound => chip8.sound_timer() as u16,
+      Self::Value(value) => value,
+      Self::ByteAtI => byte_at(chip8.i()),
+      Self::ByteAt(address) => byte_at(address),
+    }
+  }
+}
+
+impl FromStr for Operand {
+  type Err = ConditionError;
+
+  fn from_str(text: &str) -> Result<Self, ConditionError> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(address) = lower.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+      return match address.trim().parse()? {
+        Self::I => Ok(Self::ByteAtI),
+        Self::Value(address) => Ok(Self::ByteAt(address)),
+        _ => Err(ConditionError(format!("{text} isn't an address"))),
+      };
+    }
+    let operand = match lower.as_str() {
+      "i" => Self::I,
+      "pc" => Self::Pc,
+      "sp" => Self::Sp,
+      "dt" => Self::Delay,
+      "st" => Self::Sound,
+      _ => match lower.strip_prefix('v') {
+        Some(digit) if digit.len() == 1 => match usize::from_str_radix(digit, 16) {
+          Ok(index) => Self::V(index),
+          Err(_) => return Err(ConditionError(format!("unknown register {text}"))),
+        },
+        _ => {
+          let value = match lower.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => lower.parse(),
+          };
+          Self::Value(value.map_err(|_| ConditionError(format!("unknown value {text}")))?)
+        }
+      },
+    };
+    Ok(operand)
+  }
+}
+
+impl FromStr for Condition {
+  type Err = ConditionError;
+
+  fn from_str(text: &str) -> Result<Self, ConditionError> {
+    let Some(start) = text.find(['=', '!', '<', '>']) else {
+      return Err(ConditionError(format!("{text} doesn't compare anything")));
+    };
+    let rest = &text[start..];
+    let (comparison, len) = match rest.get(..2) {
+      Some("==") => (Comparison::Equal, 2),
+      Some("!=") => (Comparison::NotEqual, 2),
+      Some("<=") => (Comparison::LessOrEqual, 2),
+      Some(">=") => (Comparison::GreaterOrEqual, 2),
+      _ if rest.starts_with('<') => (Comparison::Less, 1),
+      _ if rest.starts_with('>') => (Comparison::Greater, 1),
+      _ => return Err(ConditionError(format!("unknown comparison in {text}"))),
+    };
+    Ok(Self {
+      left: text[..start].trim().parse()?,
+      comparison,
+      right: rest[len..].trim().parse()?,
+    })
+  }
+}
+
+impl fmt::Display for Condition {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let comparison = match self.comparison {
+      Comparison::Equal => "==",
+      Comparison::NotEqual => "!=",
+      Comparison::Less => "<",
+      Comparison::LessOrEqual => "<=",
+      Comparison::Greater => ">",
+      Comparison::GreaterOrEqual => ">=",
+    };
+    write!(f, "{} {comparison} {}", self.left, self.right)
+  }
+}
+
+impl fmt::Display for Operand {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::V(index) => write!(f, "V{index:X}"),
+      Self::I => write!(f, "I"),
+      Self::Pc => write!(f, "PC"),
+      Self::Sp => write!(f, "SP"),
+      Self::Delay => write!(f, "DT"),
+      Self::Sound => write!(f, "ST"),
+      Self::Value(value) => write!(f, "{value:#X}"),
+      Self::ByteAtI => write!(f, "[I]"),
+      Self::ByteAt(address) => write!(f, "[{address:#X}]"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Quirks, Watchpoint};
+
+  // 0x200: CALL 0x208; ADD V1, 0x01; JP 0x200; (padding)
+  // 0x208: ADD V3, 0x08; LD I, 0x300; LD [I], V0; RET
+  const ROM: [u8; 16] = [
+    0x22, 0x08, 0x71, 0x01, 0x12, 0x00, 0x00, 0x00, 0x73, 0x08, 0xA3, 0x00, 0xF0, 0x55, 0x00, 0xEE,
+  ];
+
+  fn machine() -> Chip8 {
+    let mut chip8 = Chip8::new(Quirks::schip_modern());
+    chip8.load_rom(&ROM).unwrap();
+    chip8
+  }
+
+  // Runs frames until the debugger stops the machine
+  fn run(debugger: &mut Debugger, chip8: &mut Chip8) -> Stop {
+    (0..100).find_map(|_| debugger.run_frame(chip8)).expect("the debugger never stopped")
+  }
+
+  #[test]
+  fn test_breakpoints() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.add_breakpoint(0x202, None);
+    assert!(matches!(run(&mut debugger, &mut chip8), Stop::Breakpoint(0x202)));
+    assert!(debugger.is_paused());
+    assert_eq!(chip8.pc(), 0x202);
+    assert!(debugger.run_frame(&mut chip8).is_none());
+
+    // Resuming runs the instruction under the breakpoint before stopping again
+    debugger.resume();
+    assert!(matches!(run(&mut debugger, &mut chip8), Stop::Breakpoint(0x202)));
+    assert_eq!(chip8.v()[1], 1);
+
+    debugger.add_breakpoint(0x202, Some("V3 >= 0x20".parse().unwrap()));
+    debugger.resume();
+    assert!(matches!(run(&mut debugger, &mut chip8), Stop::Breakpoint(0x202)));
+    assert_eq!(chip8.v()[3], 0x20);
+    assert!(debugger.remove_breakpoint(0x202));
+    assert!(debugger.breakpoints().is_empty());
+  }
+
+  #[test]
+  fn test_stepping() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.step();
+    assert!(matches!(run(&mut debugger, &mut chip8), Stop::Step));
+    assert_eq!(chip8.pc(), 0x208);
+    debugger.step_out(&chip8);
+    assert!(matches!(run(&mut debugger, &mut chip8), Stop::Step));
+    assert_eq!((chip8.pc(), chip8.stack().len()), (0x202, 0));
+
+    debugger.step_over(&chip8);
+    run(&mut debugger, &mut chip8);
+    debugger.step_over(&chip8);
+    run(&mut debugger, &mut chip8);
+    assert_eq!(chip8.pc(), 0x200);
+    // The whole subroutine runs in one step over
+    debugger.step_over(&chip8);
+    assert!(matches!(run(&mut debugger, &mut chip8), Stop::Step));
+    assert_eq!((chip8.pc(), chip8.v()[3]), (0x202, 0x10));
+
+    // A breakpoint inside the subroutine still stops it
+    debugger.add_breakpoint(0x20C, None);
+    debugger.step();
+    run(&mut debugger, &mut chip8);
+    debugger.step();
+    run(&mut debugger, &mut chip8);
+    debugger.step_over(&chip8);
+    assert!(matches!(run(&mut debugger, &mut chip8), Stop::Breakpoint(0x20C)));
+  }
+
+  #[test]
+  fn test_watchpoints() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    let watchpoint = Watchpoint { address: 0x300, len: 1, access: Access::Write };
+    chip8.memory_mut().add_watchpoint(watchpoint).unwrap();
+    match run(&mut debugger, &mut chip8) {
+      Stop::Watchpoint { pc, hit } => {
+        assert_eq!(pc, 0x20C);
+        assert_eq!(hit.address, 0x300);
+      }
+      stop => panic!("unexpected stop {stop:?}"),
+    }
+    // Stopped after the write
+    assert_eq!(chip8.pc(), 0x20E);
+
+    // Watchpoints survive loading a save state
+    let state = chip8.save_state();
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.memory().watchpoints().count(), 1);
+  }
+
+  #[test]
+  fn test_faults() {
+    let mut chip8 = Chip8::default();
+    chip8.load_rom(&[0x00, 0xEE]).unwrap();
+    let mut debugger = Debugger::new();
+    assert!(matches!(run(&mut debugger, &mut chip8), Stop::Fault(InterpreterError::StackUnderflow)));
+    assert_eq!(chip8.pc(), 0x200);
+  }
+
+  #[test]
+  fn test_conditions() {
+    let condition: Condition = "v3==8".parse().unwrap();
+    assert_eq!(condition.to_string(), "V3 == 0x8");
+    assert_eq!("[i] < 5".parse::<Condition>().unwrap().to_string(), "[I] < 0x5");
+    assert_eq!("PC >= [0x300]".parse::<Condition>().unwrap().to_string(), "PC >= [0x300]");
+    assert!("V3".parse::<Condition>().is_err());
+    assert!("VG == 1".parse::<Condition>().is_err());
+    assert!("V3 = 1".parse::<Condition>().is_err());
+    assert!("[V3] == 1".parse::<Condition>().is_err());
+
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.step_over(&chip8);
+    run(&mut debugger, &mut chip8);
+    assert!(condition.holds(&chip8));
+    assert!("[I] == 0 ".parse::<Condition>().unwrap().holds(&chip8));
+    assert!(!"SP > 0".parse::<Condition>().unwrap().holds(&chip8));
+  }
+}
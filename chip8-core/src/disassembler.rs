@@ -0,0 +1,285 @@
+use super::{
+  instructions::{Instruction, InstructionSet},
+  memory::ROM_OFFSET,
+  variant::Variant,
+};
+use core::fmt::{self, Write};
+
+// How instructions are written out. Octo listings can be fed back to an Octo
+// assembler as long as every address stays numeric.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Syntax {
+  // Cowgod's technical reference, e.g. DRW V3, V4, 5
+  #[default]
+  Mnemonic,
+  // Octo, e.g. sprite v3 v4 5
+  Octo,
+}
+
+// A linear sweep over a ROM, decoding two bytes at a time. Anything that
+// doesn't decode under the variant is listed as data.
+pub struct Disassembler<'a> {
+  rom: &'a [u8],
+  offset: usize,
+  origin: u16,
+  instructions: InstructionSet,
+  syntax: Syntax,
+}
+
+// One instruction, or bytes that aren't one
+#[derive(Clone, Copy)]
+pub struct Line<'a> {
+  pub address: u16,
+  pub bytes: &'a [u8],
+  pub instruction: Option<&'static Instruction>,
+  pub syntax: Syntax,
+}
+
+impl<'a> Disassembler<'a> {
+  // Lists a ROM as loaded at the usual 0x200
+  pub fn new(rom: &'a [u8], variant: Variant) -> Self {
+    Self {
+      rom,
+      offset: 0,
+      origin: ROM_OFFSET as u16,
+      instructions: InstructionSet::new(variant),
+      syntax: Syntax::default(),
+    }
+  }
+
+  pub fn with_syntax(mut self, syntax: Syntax) -> Self {
+    self.syntax = syntax;
+    self
+  }
+
+  // Address the first byte is listed at
+  pub fn with_origin(mut self, origin: u16) -> Self {
+    self.origin = origin;
+    self
+  }
+}
+
+impl<'a> Iterator for Disassembler<'a> {
+  type Item = Line<'a>;
+
+  fn next(&mut self) -> Option<Line<'a>> {
+    let rest = &self.rom[self.offset..];
+    if rest.is_empty() {
+      return None;
+    }
+    let instruction = rest
+      .get(..2)
+      .and_then(|bytes| self.instructions.disassemble(u16::from_be_bytes([bytes[0], bytes[1]])));
+    let len = match instruction {
+      // F000 NNNN carries its address in the next two bytes
+      Some(instruction) if instruction.name == "LD I, long" => 4,
+      _ => 2,
+    };
+    let (instruction, len) = match instruction {
+      Some(_) if rest.len() < len => (None, rest.len().min(2)),
+      _ => (instruction, len.min(rest.len())),
+    };
+    let line = Line {
+      address: self.origin.wrapping_add(self.offset as u16),
+      bytes: &rest[..len],
+      instruction,
+      syntax: self.syntax,
+    };
+    self.offset += len;
+    Some(line)
+  }
+}
+
+impl<'a> Line<'a> {
+  // Just the instruction or data, without the address and bytes
+  pub fn text(&self) -> Text<'a> {
+    Text(*self)
+  }
+}
+
+// The listing line: address, raw bytes, then the instruction
+impl fmt::Display for Line<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut raw = [0u8; 8];
+    for (index, byte) in self.bytes.iter().enumerate() {
+      raw[index * 2] = hex_digit(byte >> 4);
+      raw[index * 2 + 1] = hex_digit(byte & 0xF);
+    }
+    let raw = core::str::from_utf8(&raw[..self.bytes.len() * 2]).unwrap_or_default();
+    match self.syntax {
+      Syntax::Mnemonic => write!(f, "{:04X}  {raw:<8}  {}", self.address, self.text()),
+      Syntax::Octo => {
+        let mut counter = Counter(0);
+        write!(counter, "{}", self.text())?;
+        let padding = 24usize.saturating_sub(counter.0);
+        write!(f, "{}{:padding$}# {:04X}  {raw}", self.text(), "", self.address)
+      }
+    }
+  }
+}
+
+fn hex_digit(nibble: u8) -> u8 {
+  b"0123456789ABCDEF"[nibble as usize]
+}
+
+pub struct Text<'a>(Line<'a>);
+
+// Measures formatted text, so Octo listings can line their comments up
+struct Counter(usize);
+
+impl fmt::Write for Counter {
+  fn write_str(&mut self, s: &str) -> fmt::Result {
+    self.0 += s.len();
+    Ok(())
+  }
+}
+
+impl fmt::Display for Text<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let Line { bytes, instruction, syntax, .. } = self.0;
+    let Some(instruction) = instruction else {
+      return match syntax {
+        Syntax::Mnemonic => {
+          f.write_str("DB ")?;
+          for (index, byte) in bytes.iter().enumerate() {
+            let separator = if index == 0 { "" } else { ", " };
+            write!(f, "{separator}{byte:#04X}")?;
+          }
+          Ok(())
+        }
+        Syntax::Octo => {
+          for (index, byte) in bytes.iter().enumerate() {
+            let separator = if index == 0 { "" } else { " " };
+            write!(f, "{separator}{byte:#04X}")?;
+          }
+          Ok(())
+        }
+      };
+    };
+
+    let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let x = (opcode >> 8) & 0xF;
+    let y = (opcode >> 4) & 0xF;
+    let template = match syntax {
+      Syntax::Mnemonic => instruction.name,
+      Syntax::Octo => instruction.octo,
+    };
+    // Octo writes registers in lower case, v0 to vf
+    let register = |f: &mut fmt::Formatter, index: u16| match syntax {
+      Syntax::Mnemonic => write!(f, "V{index:X}"),
+      Syntax::Octo => write!(f, "v{index:x}"),
+    };
+    for (index, word) in template.split(' ').enumerate() {
+      if index > 0 {
+        f.write_str(" ")?;
+      }
+      let (word, comma) = match word.strip_suffix(',') {
+        Some(word) => (word, ","),
+        None => (word, ""),
+      };
+      match word {
+        "Vx" | "vx" => register(f, x)?,
+        "Vy" | "vy" => register(f, y)?,
+        "Vx-Vy" => {
+          register(f, x)?;
+          f.write_str("-")?;
+          register(f, y)?
+        }
+        "byte" => write!(f, "{:#04X}", opcode & 0xFF)?,
+        // Octo spells F000 NNNN "i := long addr", where long is a keyword
+        "long" | "addr" if bytes.len() == 4 && (word == "addr") == (syntax == Syntax::Octo) => {
+          write!(f, "{:#06X}", u16::from_be_bytes([bytes[2], bytes[3]]))?
+        }
+        "addr" => write!(f, "{:#05X}", opcode & 0xFFF)?,
+        "nibble" => write!(f, "{}", opcode & 0xF)?,
+        // PLANE takes its mask from the x field
+        "n" => write!(f, "{x}")?,
+        _ => f.write_str(word)?,
+      }
+      f.write_str(comma)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn listing(rom: &[u8], variant: Variant, syntax: Syntax) -> Vec<String> {
+    Disassembler::new(rom, variant).with_syntax(syntax).map(|line| format!("{line}")).collect()
+  }
+
+  fn text(rom: &[u8], variant: Variant, syntax: Syntax) -> Vec<String> {
+    Disassembler::new(rom, variant).with_syntax(syntax).map(|line| format!("{}", line.text())).collect()
+  }
+
+  #[test]
+  fn test_mnemonics() {
+    let rom = [
+      0x00, 0xE0, 0xA2, 0x2A, 0x6A, 0x0C, 0xD3, 0x45, 0x83, 0x4E, 0xF5, 0x65, 0xB3, 0x00, 0x01, 0x23,
+    ];
+    assert_eq!(
+      text(&rom, Variant::Chip8, Syntax::Mnemonic),
+      [
+        "CLS",
+        "LD I, 0x22A",
+        "LD VA, 0x0C",
+        "DRW V3, V4, 5",
+        "SHL V3",
+        "LD V5, [I]",
+        "JP V0, 0x300",
+        "DB 0x01, 0x23",
+      ]
+    );
+    assert_eq!(
+      text(&rom, Variant::Chip8, Syntax::Octo),
+      [
+        "clear",
+        "i := 0x22A",
+        "va := 0x0C",
+        "sprite v3 v4 5",
+        "v3 <<= v4",
+        "load v5",
+        "jump0 0x300",
+        "0x01 0x23",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_variants() {
+    let rom = [0x00, 0xFF, 0x52, 0x43, 0xF3, 0x01, 0xF0, 0x00, 0x12, 0x34];
+    assert_eq!(
+      text(&rom, Variant::Chip8, Syntax::Mnemonic),
+      ["DB 0x00, 0xFF", "DB 0x52, 0x43", "DB 0xF3, 0x01", "DB 0xF0, 0x00", "JP 0x234"]
+    );
+    assert_eq!(
+      text(&rom, Variant::XoChip, Syntax::Mnemonic),
+      ["HIGH", "LD V2-V4, [I]", "PLANE 3", "LD I, 0x1234"]
+    );
+    assert_eq!(
+      text(&rom, Variant::XoChip, Syntax::Octo),
+      ["hires", "load v2 - v4", "plane 3", "i := long 0x1234"]
+    );
+  }
+
+  #[test]
+  fn test_listing() {
+    let rom = [0x12, 0x00, 0xF0, 0x00, 0xFF];
+    assert_eq!(
+      listing(&rom, Variant::XoChip, Syntax::Mnemonic),
+      ["0200  1200      JP 0x200", "0202  F000      DB 0xF0, 0x00", "0204  FF        DB 0xFF"]
+    );
+    assert_eq!(
+      listing(&rom, Variant::XoChip, Syntax::Octo),
+      [
+        "jump 0x200              # 0200  1200",
+        "0xF0 0x00               # 0202  F000",
+        "0xFF                    # 0204  FF",
+      ]
+    );
+    let lines: Vec<_> = Disassembler::new(&rom, Variant::Chip8).with_origin(0x300).collect();
+    assert_eq!(lines[1].address, 0x302);
+  }
+}
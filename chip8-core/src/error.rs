@@ -0,0 +1,50 @@
+use super::memory::MAX_WATCHPOINTS;
+use core::fmt;
+
+// TODO: Implement PartialEq
+#[derive(Debug)]
+pub enum InterpreterError {
+  InvalidInstructionError(usize, u16),
+  InvalidAddressError(usize),
+  InvalidRegister(usize),
+  InvalidFrameBufferIndex(u16),
+  InvalidKey(usize),
+  StackOverflow,
+  StackUnderflow,
+  InvalidStateHeader,
+  UnsupportedStateVersion(u16),
+  StateChecksumMismatch,
+  CorruptState(&'static str),
+  TooManyWatchpoints,
+  HiresUnsupported,
+}
+
+// Written out by hand rather than derived, so the core builds without std
+impl fmt::Display for InterpreterError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::InvalidInstructionError(addr, opcode) => {
+        write!(f, "Invalid instruction {opcode:#06x} at address {addr:#06x}")
+      }
+      Self::InvalidAddressError(addr) => write!(f, "Invalid address {addr:#06x}"),
+      Self::InvalidRegister(index) => write!(f, "Invalid register address {index}"),
+      Self::InvalidFrameBufferIndex(index) => write!(f, "Invalid frame buffer index {index}"),
+      Self::InvalidKey(key) => write!(f, "Invalid key {key}"),
+      Self::StackOverflow => write!(f, "Stack overflow"),
+      Self::StackUnderflow => write!(f, "Stack underflow"),
+      Self::InvalidStateHeader => write!(f, "Not a save state"),
+      Self::UnsupportedStateVersion(version) => {
+        write!(f, "Unsupported save state version {version}")
+      }
+      Self::StateChecksumMismatch => write!(f, "Save state checksum mismatch"),
+      Self::CorruptState(reason) => write!(f, "Corrupt save state: {reason}"),
+      Self::TooManyWatchpoints => write!(f, "Too many watchpoints, the most is {MAX_WATCHPOINTS}"),
+      Self::HiresUnsupported => write!(f, "The frame buffer is too small for hires"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InterpreterError {}
+
+pub type InterpretterResult<T = ()> = Result<T, InterpreterError>;
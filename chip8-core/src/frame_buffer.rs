@@ -0,0 +1,282 @@
+use super::error::*;
+use core::{
+  fmt::{self, Write},
+  ops::{Deref, DerefMut},
+};
+
+pub const LORES_WIDTH: u16 = 64;
+pub const LORES_HEIGHT: u16 = 32;
+pub const HIRES_WIDTH: u16 = 128;
+pub const HIRES_HEIGHT: u16 = 64;
+pub const PLANE_COUNT: u8 = 2;
+pub const LORES_PIXELS: usize = LORES_WIDTH as usize * LORES_HEIGHT as usize;
+pub const HIRES_PIXELS: usize = HIRES_WIDTH as usize * HIRES_HEIGHT as usize;
+// Text rendering of each combination of the two bitplanes
+const ASCII_PIXELS: [char; 4] = ['.', '#', '+', '%'];
+
+// Each pixel holds one bit per bitplane, so with XO-CHIP's two planes a pixel
+// is a colour index between 0 and 3. Like `Memory`, the pixel storage is
+// generic so a machine that never leaves lores can get by with 2 KB.
+pub struct FrameBuffer<P: ?Sized = [u8]> {
+  hires: bool,
+  planes: u8,
+  pixels: P,
+}
+
+impl<const PIXELS: usize> Default for FrameBuffer<[u8; PIXELS]> {
+  fn default() -> Self {
+    const { assert!(PIXELS >= LORES_PIXELS, "frame buffer must hold at least a lores display") };
+    Self {
+      hires: false,
+      planes: 1,
+      pixels: [0; PIXELS],
+    }
+  }
+}
+
+impl<const PIXELS: usize> Deref for FrameBuffer<[u8; PIXELS]> {
+  type Target = FrameBuffer;
+
+  fn deref(&self) -> &FrameBuffer {
+    self
+  }
+}
+
+impl<const PIXELS: usize> DerefMut for FrameBuffer<[u8; PIXELS]> {
+  fn deref_mut(&mut self) -> &mut FrameBuffer {
+    self
+  }
+}
+
+impl FrameBuffer {
+  pub fn width(&self) -> u16 {
+    if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+  }
+
+  pub fn height(&self) -> u16 {
+    if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+  }
+
+  fn len(&self) -> usize {
+    self.width() as usize * self.height() as usize
+  }
+
+  pub fn hires(&self) -> bool {
+    self.hires
+  }
+
+  // Fails if the buffer is too small for hires
+  pub fn set_hires(&mut self, hires: bool) -> InterpretterResult {
+    if hires && self.pixels.len() < HIRES_PIXELS {
+      return Err(InterpreterError::HiresUnsupported);
+    }
+    self.hires = hires;
+    self.pixels.fill(0);
+    Ok(())
+  }
+
+  pub fn planes(&self) -> u8 {
+    self.planes
+  }
+
+  pub fn set_planes(&mut self, planes: u8) {
+    self.planes = planes & ((1 << PLANE_COUNT) - 1);
+  }
+
+  // Replaces the display contents wholesale, used to restore save states.
+  // `frame` must be exactly one pixel per cell at the given resolution.
+  pub fn restore(&mut self, hires: bool, planes: u8, frame: &[u8]) -> InterpretterResult {
+    if frame.iter().any(|&pixel| pixel >> PLANE_COUNT != 0) {
+      return Err(InterpreterError::CorruptState("pixel outside the bitplanes"));
+    }
+    self.set_hires(hires)?;
+    self.set_planes(planes);
+    let len = self.len();
+    self.pixels[..len].copy_from_slice(frame);
+    Ok(())
+  }
+
+  // Reads the pixel in the selected planes
+  pub fn get_i(&self, index: u16) -> InterpretterResult<bool> {
+    if (index as usize) < self.len() {
+      Ok(self.pixels[index as usize] & self.planes != 0)
+    } else {
+      Err(InterpreterError::InvalidFrameBufferIndex(index))
+    }
+  }
+
+  // Sets or clears the pixel in the selected planes
+  pub fn set_i(&mut self, index: u16, value: bool) -> InterpretterResult {
+    if (index as usize) < self.len() {
+      let pixel = &mut self.pixels[index as usize];
+      if value {
+        *pixel |= self.planes;
+      } else {
+        *pixel &= !self.planes;
+      }
+      Ok(())
+    } else {
+      Err(InterpreterError::InvalidFrameBufferIndex(index))
+    }
+  }
+
+  // Flips the pixel in a single plane, returning true if it was switched off
+  pub fn xor_xy(&mut self, x: u16, y: u16, plane: u8) -> InterpretterResult<bool> {
+    let index = (y * self.width()) + x;
+    if (index as usize) < self.len() {
+      let pixel = &mut self.pixels[index as usize];
+      *pixel ^= plane;
+      Ok(*pixel & plane == 0)
+    } else {
+      Err(InterpreterError::InvalidFrameBufferIndex(index))
+    }
+  }
+
+  pub fn get_xy(&self, x: u16, y: u16) -> InterpretterResult<bool> {
+    self.get_i((y * self.width()) + x)
+  }
+
+  pub fn set_xy(&mut self, x: u16, y: u16, value: bool) -> InterpretterResult {
+    self.set_i((y * self.width()) + x, value)
+  }
+
+  pub fn frame(&self) ->  &[u8] {
+    &self.pixels[..self.len()]
+  }
+
+  pub fn clear(&mut self) {
+    let planes = self.planes;
+    self.pixels.iter_mut().for_each(|pixel| *pixel &= !planes);
+  }
+
+  pub fn scroll_up(&mut self, rows: u16) {
+    self.scroll(0, -(rows as i32));
+  }
+
+  pub fn scroll_down(&mut self, rows: u16) {
+    self.scroll(0, rows as i32);
+  }
+
+  pub fn scroll_left(&mut self, columns: u16) {
+    self.scroll(-(columns as i32), 0);
+  }
+
+  pub fn scroll_right(&mut self, columns: u16) {
+    self.scroll(columns as i32, 0);
+  }
+
+  // Moves the selected planes by dx, dy. Pixels scrolled off the edge are lost.
+  // Works in place, walking away from the direction of travel so every pixel
+  // is read before it's overwritten.
+  fn scroll(&mut self, dx: i32, dy: i32) {
+    let width = self.width() as i32;
+    let height = self.height() as i32;
+    let planes = self.planes;
+    let order = |n: i32, delta: i32| (0..n).map(move |i| if delta > 0 { n - 1 - i } else { i });
+    for y in order(height, dy) {
+      for x in order(width, dx) {
+        let (sx, sy) = (x - dx, y - dy);
+        let moved = if (0..width).contains(&sx) && (0..height).contains(&sy) {
+          self.pixels[(sy * width + sx) as usize] & planes
+        } else {
+          0
+        };
+        let pixel = &mut self.pixels[(y * width + x) as usize];
+        *pixel = (*pixel & !planes) | moved;
+      }
+    }
+  }
+}
+
+// Draws the display as text, one character per pixel and one line per row
+impl fmt::Display for FrameBuffer {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for row in self.frame().chunks(self.width() as usize) {
+      for &pixel in row {
+        f.write_char(ASCII_PIXELS[pixel as usize])?;
+      }
+      f.write_char('\n')?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_resolution() {
+    let mut frame_buffer: FrameBuffer<[u8; HIRES_PIXELS]> = FrameBuffer::default();
+    assert_eq!(frame_buffer.frame().len(), 2048);
+    assert!(frame_buffer.set_xy(63, 31, true).is_ok());
+    assert!(frame_buffer.set_xy(0, 32, true).is_err());
+    frame_buffer.set_hires(true).unwrap();
+    assert_eq!(frame_buffer.frame().len(), 8192);
+    assert!(!frame_buffer.get_xy(63, 31).unwrap());
+    assert!(frame_buffer.set_xy(127, 63, true).is_ok());
+    frame_buffer.set_hires(false).unwrap();
+    assert!(frame_buffer.frame().iter().all(|&pixel| pixel == 0));
+  }
+
+  #[test]
+  fn test_scroll() {
+    let mut frame_buffer: FrameBuffer<[u8; HIRES_PIXELS]> = FrameBuffer::default();
+    frame_buffer.set_xy(10, 10, true).unwrap();
+    frame_buffer.scroll_down(4);
+    assert!(frame_buffer.get_xy(10, 14).unwrap());
+    assert!(!frame_buffer.get_xy(10, 10).unwrap());
+    frame_buffer.scroll_right(4);
+    assert!(frame_buffer.get_xy(14, 14).unwrap());
+    frame_buffer.scroll_left(8);
+    assert!(frame_buffer.get_xy(6, 14).unwrap());
+    assert_eq!(frame_buffer.frame().iter().filter(|&&pixel| pixel == 1).count(), 1);
+    // Pixels scrolled off the edge are lost
+    frame_buffer.scroll_left(8);
+    frame_buffer.scroll_down(32);
+    assert!(frame_buffer.frame().iter().all(|&pixel| pixel == 0));
+  }
+
+  #[test]
+  fn test_planes() {
+    let mut frame_buffer: FrameBuffer<[u8; HIRES_PIXELS]> = FrameBuffer::default();
+    assert!(!frame_buffer.xor_xy(1, 1, 1).unwrap());
+    assert!(!frame_buffer.xor_xy(1, 1, 2).unwrap());
+    assert_eq!(frame_buffer.frame()[64 + 1], 3);
+    frame_buffer.set_planes(2);
+    assert!(frame_buffer.get_xy(1, 1).unwrap());
+    frame_buffer.scroll_down(1);
+    assert_eq!(frame_buffer.frame()[64 + 1], 1);
+    assert_eq!(frame_buffer.frame()[128 + 1], 2);
+    frame_buffer.clear();
+    assert_eq!(frame_buffer.frame()[64 + 1], 1);
+    assert_eq!(frame_buffer.frame()[128 + 1], 0);
+    frame_buffer.set_planes(1);
+    assert!(frame_buffer.xor_xy(1, 1, 1).unwrap());
+    assert!(frame_buffer.frame().iter().all(|&pixel| pixel == 0));
+  }
+
+  #[test]
+  fn test_display() {
+    let mut frame_buffer: FrameBuffer<[u8; HIRES_PIXELS]> = FrameBuffer::default();
+    frame_buffer.xor_xy(0, 0, 1).unwrap();
+    frame_buffer.xor_xy(1, 0, 2).unwrap();
+    frame_buffer.xor_xy(2, 0, 1).unwrap();
+    frame_buffer.xor_xy(2, 0, 2).unwrap();
+    let text = frame_buffer.to_string();
+    assert_eq!(text.lines().count(), 32);
+    assert!(text.lines().all(|line| line.len() == 64));
+    assert!(text.starts_with("#+%...."));
+  }
+
+  #[test]
+  fn test_lores_only() {
+    let mut frame_buffer: FrameBuffer<[u8; LORES_PIXELS]> = FrameBuffer::default();
+    assert!(frame_buffer.set_xy(63, 31, true).is_ok());
+    assert!(matches!(frame_buffer.set_hires(true), Err(InterpreterError::HiresUnsupported)));
+    assert_eq!(frame_buffer.frame().len(), LORES_PIXELS);
+    assert!(frame_buffer.get_xy(63, 31).unwrap());
+    frame_buffer.scroll_left(4);
+    assert!(frame_buffer.get_xy(59, 31).unwrap());
+  }
+}
@@ -0,0 +1,2106 @@
+use super::{
+  error::InterpreterError,
+  frame_buffer::{FrameBuffer, PLANE_COUNT},
+  memory::{Memory, BIG_FONT_OFFSET, FONT_OFFSET},
+  quirks::Quirks,
+  registers::{KeyWait, Registers},
+  rng::RandomSource,
+  variant::Variant,
+};
+
+pub type ExecuteFn = fn(
+  u16,
+  &mut Memory,
+  &mut Registers,
+  &mut FrameBuffer,
+  &mut dyn RandomSource,
+  &Quirks,
+) -> Result<(), InterpreterError>;
+
+pub struct Instruction {
+  // Mnemonic templates for the disassembler, in Cowgod's syntax and Octo's.
+  // Operands are named after the opcode fields they come from.
+  pub name: &'static str,
+  pub octo: &'static str,
+  pub id: u16,
+  pub mask: u16,
+  pub execute: ExecuteFn,
+}
+
+// Instruction tables, most specific first. Each variant's set is its own table
+// followed by the tables of the variants it extends.
+pub struct InstructionSet(&'static [&'static [Instruction]]);
+
+impl Default for InstructionSet {
+  fn default() -> Self {
+    Self::new(Variant::default())
+  }
+}
+
+const XO_CHIP_INSTRUCTIONS: [Instruction; 7] = [
+  scu_nibble(),
+  ld_arr_i_vx_vy(),
+  ld_arr_vx_vy_i(),
+  ld_i_long(),
+  plane_n(),
+  audio(),
+  pitch_vx(),
+];
+
+// These come before the base set so that DRW Vx, Vy, 0 takes precedence over
+// the regular DRW Vx, Vy, nibble
+const SUPER_CHIP_INSTRUCTIONS: [Instruction; 10] = [
+  scd_nibble(),
+  scr(),
+  scl(),
+  exit(),
+  low(),
+  high(),
+  drw_vx_vy_0(),
+  ld_hf_vx(),
+  ld_r_vx(),
+  ld_vx_r(),
+];
+
+const CHIP8_INSTRUCTIONS: [Instruction; 35] = [
+  sys_addr(),
+  cls(),
+  ret(),
+  jp_addr(),
+  call_addr(),
+  se_vx_byte(),
+  sne_vx_byte(),
+  se_vx_vy(),
+  ld_vx_byte(),
+  add_vx_byte(),
+  ld_vx_vy(),
+  or_vx_vy(),
+  and_vx_vy(),
+  xor_vx_vy(),
+  add_vx_vy(),
+  sub_vx_vy(),
+  shr_vx(),
+  subn_vx_vy(),
+  shl_vx(),
+  sne_vx_vy(),
+  ld_i_addr(),
+  jp_v0_addr(),
+  rnd_vx_vyte(),
+  drw_vx_vy_nibble(),
+  skp_vx(),
+  sknp_vx(),
+  ld_vx_dt(),
+  ld_vx_k(),
+  ld_dt_vx(),
+  ld_st_vx(),
+  add_i_vx(),
+  ld_f_vx(),
+  ld_b_vx(),
+  ld_arr_i_vx(),
+  ld_arr_vx_i(),
+];
+
+impl InstructionSet {
+  pub fn new(variant: Variant) -> Self {
+    Self(match variant {
+      Variant::Chip8 => &[&CHIP8_INSTRUCTIONS],
+      Variant::SuperChip => &[&SUPER_CHIP_INSTRUCTIONS, &CHIP8_INSTRUCTIONS],
+      Variant::XoChip => &[&XO_CHIP_INSTRUCTIONS, &SUPER_CHIP_INSTRUCTIONS, &CHIP8_INSTRUCTIONS],
+    })
+  }
+
+  pub fn execute(
+    &self,
+    mem: &mut Memory,
+    registers: &mut Registers,
+    frame_buffer: &mut FrameBuffer,
+    rng: &mut dyn RandomSource,
+    quirks: &Quirks,
+  ) -> Result<(), InterpreterError> {
+    let pc = registers.pc as usize;
+
+    let opbytes = mem.peek(pc, 2)?;
+    let opcode = ((opbytes[0] as u16) << 8) | (opbytes[1] as u16);
+
+    match self.disassemble(opcode) {
+      Some(instr) => {
+        (instr.execute)(opcode, mem, registers, frame_buffer, rng, quirks)
+      },
+      None => {
+        Err(InterpreterError::InvalidInstructionError(pc, opcode))
+      }
+    }
+  }
+
+  // Finds the instruction an opcode decodes to under this set
+  pub fn disassemble(&self, opcode: u16) -> Option<&'static Instruction> {
+    self.0.iter().flat_map(|table| table.iter()).find(|instr| {
+      (opcode & instr.mask) == instr.id
+    })
+  }
+}
+
+// Moves past the instruction following the current one. XO-CHIP's F000 NNNN
+// is four bytes long, so skipping it has to step over the address too.
+fn skip(mem: &Memory, registers: &mut Registers) {
+  let next = registers.pc as usize + 2;
+  let long = mem.peek(next, 2).map(|bytes| bytes == [0xF0, 0x00]).unwrap_or(false);
+  registers.pc += if long { 6 } else { 4 };
+}
+
+const fn sys_addr() -> Instruction {
+  Instruction {
+    name: "SYS addr",
+    octo: "0x00 0x00",
+    id: 0x0000,
+    mask: 0xFFFF,
+    execute: |_opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn cls() -> Instruction {
+  Instruction {
+    name: "CLS",
+    octo: "clear",
+    id: 0x00E0,
+    mask: 0xFFFF,
+    execute: |_opcode, _mem, registers, frame_buffer, _rng, _quirks| {
+      frame_buffer.clear();
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ret() -> Instruction {
+  Instruction {
+    name: "RET",
+    octo: "return",
+    id: 0x00EE,
+    mask: 0xFFFF,
+    execute: |_opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      registers.pc = registers.pop()? + 2;
+      Ok(())
+    }
+  }
+}
+
+const fn jp_addr() -> Instruction {
+  Instruction {
+    name: "JP addr",
+    octo: "jump addr",
+    id: 0x1000,
+    mask: 0xF000,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let nnn = opcode & 0x0FFF;
+      registers.pc = nnn;
+      Ok(())
+    }
+  }
+}
+
+const fn call_addr() -> Instruction {
+  Instruction {
+    name: "CALL addr",
+    octo: ":call addr",
+    id: 0x2000,
+    mask: 0xF000,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let nnn = opcode & 0x0FFF;
+      registers.push(registers.pc)?;
+      registers.pc = nnn;
+      Ok(())
+    }
+  }
+}
+
+const fn se_vx_byte() -> Instruction {
+  Instruction {
+    name: "SE Vx, byte",
+    octo: "if vx != byte then",
+    id: 0x3000,
+    mask: 0xF000,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let nn = (opcode & 0x00FF) as u8;
+      let vx = registers.get_v(x)?;
+      if vx == nn {
+        skip(mem, registers);
+      } else {
+        registers.pc += 2;
+      }
+      Ok(())
+    }
+  }
+}
+
+const fn sne_vx_byte() -> Instruction {
+  Instruction {
+    name: "SNE Vx, byte",
+    octo: "if vx == byte then",
+    id: 0x4000,
+    mask: 0xF000,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let nn = (opcode & 0x00FF) as u8;
+      let vx = registers.get_v(x)?;
+      if vx != nn {
+        skip(mem, registers);
+      } else {
+        registers.pc += 2;
+      }
+      Ok(())
+    }
+  }
+}
+
+const fn se_vx_vy() -> Instruction {
+  Instruction {
+    name: "SE Vx, Vy",
+    octo: "if vx != vy then",
+    id: 0x5000,
+    mask: 0xF00F,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let vx = registers.get_v(((opcode & 0x0F00) >> 8) as usize)?;
+      let vy = registers.get_v(((opcode & 0x00F0) >> 4) as usize)?;
+      if vx == vy {
+        skip(mem, registers);
+      } else {
+        registers.pc += 2;
+      }
+      Ok(())
+    }
+  }
+}
+
+const fn ld_vx_byte() -> Instruction {
+  Instruction {
+    name: "LD Vx, byte",
+    octo: "vx := byte",
+    id: 0x6000,
+    mask: 0xF000,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let nn = (opcode & 0x00FF) as u8;
+      registers.set_v(x, nn)?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn add_vx_byte() -> Instruction {
+  Instruction {
+    name: "ADD Vx, byte",
+    octo: "vx += byte",
+    id: 0x7000,
+    mask: 0xF000,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let nn = opcode & 0x00FF;
+      let vx = registers.get_v(x)? as u16;
+      registers.set_v(x, (vx + nn) as u8)?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_vx_vy() -> Instruction {
+  Instruction {
+    name: "LD Vx, Vy",
+    octo: "vx := vy",
+    id: 0x8000,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      registers.set_v(x, registers.get_v(y)?)?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn or_vx_vy() -> Instruction {
+  Instruction {
+    name: "OR Vx, Vy",
+    octo: "vx |= vy",
+    id: 0x8001,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let vx = registers.get_v(x)?;
+      let vy = registers.get_v(y)?;
+      registers.set_v(x, vx | vy)?;
+      if quirks.vf_reset {
+        registers.set_vf(0);
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn and_vx_vy() -> Instruction {
+  Instruction {
+    name: "AND Vx, Vy",
+    octo: "vx &= vy",
+    id: 0x8002,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let vx = registers.get_v(x)?;
+      let vy = registers.get_v(y)?;
+      registers.set_v(x, vx & vy)?;
+      if quirks.vf_reset {
+        registers.set_vf(0);
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn xor_vx_vy() -> Instruction {
+  Instruction {
+    name: "XOR Vx, Vy",
+    octo: "vx ^= vy",
+    id: 0x8003,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let vx = registers.get_v(x)?;
+      let vy = registers.get_v(y)?;
+      registers.set_v(x, vx ^ vy)?;
+      if quirks.vf_reset {
+        registers.set_vf(0);
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn add_vx_vy() -> Instruction {
+  Instruction {
+    name: "ADD Vx, Vy",
+    octo: "vx += vy",
+    id: 0x8004,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let vx = registers.get_v(x)?;
+      let vy = registers.get_v(y)?;
+      let sum = vx as u16 + vy as u16;
+      registers.set_v(x, sum as u8)?;
+      registers.set_vf(if sum > 255 { 1 } else { 0 });
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn sub_vx_vy() -> Instruction {
+  Instruction {
+    name: "SUB Vx, Vy",
+    octo: "vx -= vy",
+    id: 0x8005,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let vx = registers.get_v(x)?;
+      let vy = registers.get_v(y)?;
+      // VF is NOT borrow, so an equal subtraction sets it
+      registers.set_v(x, vx.wrapping_sub(vy))?;
+      registers.set_vf(if vx >= vy { 1 } else { 0 });
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn shr_vx() -> Instruction {
+  Instruction {
+    name: "SHR Vx",
+    octo: "vx >>= vy",
+    id: 0x8006,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let v = registers.get_v(if quirks.shifting { x } else { y })?;
+      registers.set_v(x, v >> 1)?;
+      registers.set_vf(v & 1);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn subn_vx_vy() -> Instruction {
+  Instruction {
+    name: "SUBN Vx, Vy",
+    octo: "vx =- vy",
+    id: 0x8007,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let vx = registers.get_v(x)?;
+      let vy = registers.get_v(y)?;
+      registers.set_v(x, vy.wrapping_sub(vx))?;
+      registers.set_vf(if vy >= vx { 1 } else { 0 });
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn shl_vx() -> Instruction {
+  Instruction {
+    name: "SHL Vx",
+    octo: "vx <<= vy",
+    id: 0x800E,
+    mask: 0xF00F,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let v = registers.get_v(if quirks.shifting { x } else { y })?;
+      registers.set_v(x, v << 1)?;
+      registers.set_vf(v >> 7);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn sne_vx_vy() -> Instruction {
+  Instruction {
+    name: "SNE Vx, Vy",
+    octo: "if vx == vy then",
+    id: 0x9000,
+    mask: 0xF00F,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let y = ((opcode & 0x00F0) >> 4) as usize;
+      let vx = registers.get_v(x)?;
+      let vy = registers.get_v(y)?;
+      if vx != vy {
+        skip(mem, registers);
+      } else {
+        registers.pc += 2;
+      }
+      Ok(())
+    }
+  }
+}
+
+const fn ld_i_addr() -> Instruction {
+  Instruction {
+    name: "LD I, addr",
+    octo: "i := addr",
+    id: 0xA000,
+    mask: 0xF000,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let nnn = opcode & 0x0FFF;
+      registers.i = nnn;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn jp_v0_addr() -> Instruction {
+  Instruction {
+    name: "JP V0, addr",
+    octo: "jump0 addr",
+    id: 0xB000,
+    mask: 0xF000,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, quirks| {
+      let nnn = opcode & 0x0FFF;
+      let x = if quirks.jumping { ((opcode & 0x0F00) >> 8) as usize } else { 0 };
+      let v = registers.get_v(x)? as u16;
+      registers.pc = nnn + v;
+      Ok(())
+    }
+  }
+}
+
+const fn rnd_vx_vyte() -> Instruction {
+  Instruction {
+    name: "RND Vx, byte",
+    octo: "vx := random byte",
+    id: 0xC000,
+    mask: 0xF000,
+    execute: |opcode, _mem, registers, _frame_buffer, rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let nn = (opcode & 0x00FF) as u8;
+      registers.set_v(x, rng.next_u8() & nn)?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+fn draw_sprite(
+  mem: &Memory,
+  registers: &mut Registers,
+  frame_buffer: &mut FrameBuffer,
+  quirks: &Quirks,
+  opcode: u16,
+  width: u16,
+  height: u16,
+) -> Result<(), InterpreterError> {
+  let x = ((opcode & 0x0F00) >> 8) as usize;
+  let y = ((opcode & 0x00F0) >> 4) as usize;
+  let screen_width = frame_buffer.width();
+  let screen_height = frame_buffer.height();
+  let vx = registers.get_v(x)? as u16 % screen_width;
+  let vy = registers.get_v(y)? as u16 % screen_height;
+  let row_bytes = width / 8;
+  let planes = frame_buffer.planes();
+  let mut addr = registers.i;
+
+  registers.v[15] = 0;
+  // With more than one plane selected, the sprite data for each plane
+  // follows the previous one in memory
+  for plane in (0..PLANE_COUNT).map(|p| 1 << p).filter(|p| planes & p != 0) {
+    for yi in 0..height {
+      let mut py = vy + yi;
+      if py >= screen_height && !quirks.clipping {
+        py %= screen_height;
+      }
+      if py < screen_height {
+        for xi in 0..width {
+          let byte = mem.read_byte(addr.wrapping_add(yi * row_bytes + xi / 8) as usize)?;
+          let mut px = vx + xi;
+          if px >= screen_width && !quirks.clipping {
+            px %= screen_width;
+          }
+          // Flip pixel if sprite bit is set, and set the VF register if a
+          // pixel is unset
+          if px < screen_width
+            && (byte & (0x80 >> (xi % 8))) != 0
+            && frame_buffer.xor_xy(px, py, plane)? {
+            registers.v[15] = 1;
+          }
+        }
+      }
+    }
+    addr = addr.wrapping_add(height * row_bytes);
+  }
+
+  // SUPER-CHIP only waits for the vertical blank in low resolution mode
+  if quirks.display_wait && !frame_buffer.hires() {
+    registers.display_wait = true;
+  }
+  registers.pc += 2;
+  Ok(())
+}
+
+const fn drw_vx_vy_nibble() -> Instruction {
+  Instruction {
+    name: "DRW Vx, Vy, nibble",
+    octo: "sprite vx vy nibble",
+    id: 0xD000,
+    mask: 0xF000,
+    execute: |opcode, mem, registers, frame_buffer, _rng, quirks| {
+      let n = opcode & 0x000F;
+      draw_sprite(mem, registers, frame_buffer, quirks, opcode, 8, n)
+    }
+  }
+}
+
+const fn skp_vx() -> Instruction {
+  Instruction {
+    name: "SKP Vx",
+    octo: "if vx -key then",
+    id: 0xE09E,
+    mask: 0xF0FF,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let vx = registers.get_v(x)? as usize;
+      if registers.keydown(vx)? {
+        skip(mem, registers);
+      } else {
+        registers.pc += 2;
+      }
+      Ok(())
+    }
+  }
+}
+
+const fn sknp_vx() -> Instruction {
+  Instruction {
+    name: "SKNP Vx",
+    octo: "if vx key then",
+    id: 0xE0A1,
+    mask: 0xF0FF,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let vx = registers.get_v(x)? as usize;
+      if registers.keydown(vx)? {
+        registers.pc += 2;
+      } else {
+        skip(mem, registers);
+      }
+      Ok(())
+    }
+  }
+}
+
+const fn ld_vx_dt() -> Instruction {
+  Instruction {
+    name: "LD Vx, DT",
+    octo: "vx := delay",
+    id: 0xF007,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      registers.set_v(x, registers.get_dt())?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_vx_k() -> Instruction {
+  Instruction {
+    name: "LD Vx, K",
+    octo: "vx := key",
+    id: 0xF00A,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      if quirks.key_release {
+        // Wait for a key to be pressed, then for that same key to be released.
+        // A key pressed and released between two cycles completes it at once.
+        let released = match registers.key_wait {
+          None => {
+            // Releases from before the wait started don't count
+            registers.clear_released();
+            None
+          }
+          Some(KeyWait::Press) => registers.first_released(),
+          Some(KeyWait::Release(key)) => registers.take_released(key).then_some(key),
+        };
+        match released {
+          Some(key) => {
+            registers.key_wait = None;
+            registers.set_v(x, key as u8)?;
+            registers.pc += 2;
+          }
+          None => {
+            if registers.key_wait.is_none_or(|wait| wait == KeyWait::Press) {
+              registers.key_wait = Some(registers.first_keydown().map_or(KeyWait::Press, KeyWait::Release));
+            }
+          }
+        }
+      } else if let Some(key) = registers.first_keydown() {
+        registers.set_v(x, key as u8)?;
+        registers.pc += 2;
+      }
+      Ok(())
+    }
+  }
+}
+
+const fn ld_dt_vx() -> Instruction {
+  Instruction {
+    name: "LD DT, Vx",
+    octo: "delay := vx",
+    id: 0xF015,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      registers.set_dt(registers.get_v(x)?);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_st_vx() -> Instruction {
+  Instruction {
+    name: "LD ST, Vx",
+    octo: "buzzer := vx",
+    id: 0xF018,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      registers.set_st(registers.get_v(x)?);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn add_i_vx() -> Instruction {
+  Instruction {
+    name: "ADD I, Vx",
+    octo: "i += vx",
+    id: 0xF01E,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      registers.i = registers.i.wrapping_add(registers.get_v(x)? as u16);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_f_vx() -> Instruction {
+  Instruction {
+    name: "LD F, Vx",
+    octo: "i := hex vx",
+    id: 0xF029,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let vx = registers.get_v(x)? as u16;
+      if vx > 0xF {
+        Err(InterpreterError::InvalidKey(vx as usize))
+      } else {
+        registers.i = FONT_OFFSET as u16 + (vx * 5);
+        registers.pc += 2;
+        Ok(())
+      }
+    }
+  }
+}
+
+const fn ld_b_vx() -> Instruction {
+  Instruction {
+    name: "LD B, Vx",
+    octo: "bcd vx",
+    id: 0xF033,
+    mask: 0xF0FF,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let vx = registers.get_v(x)?;
+      mem.write_byte(registers.i as usize, vx / 100)?;
+      mem.write_byte(registers.i.wrapping_add(1) as usize, (vx / 10) % 10)?;
+      mem.write_byte(registers.i.wrapping_add(2) as usize, (vx % 100) % 10)?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_arr_i_vx() -> Instruction {
+  Instruction {
+    name: "LD [I], Vx",
+    octo: "save vx",
+    id: 0xF055,
+    mask: 0xF0FF,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, quirks| {
+      let x = (opcode & 0x0F00) >> 8;
+      for i in 0..(x + 1) {
+        mem.write_byte(
+          registers.i.wrapping_add(i) as usize,
+          registers.get_v(i as usize)?
+        )?;
+      }
+      if quirks.memory {
+        registers.i = registers.i.wrapping_add(if quirks.memory_increment_by_x { x } else { x + 1 });
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_arr_vx_i() -> Instruction {
+  Instruction {
+    name: "LD Vx, [I]",
+    octo: "load vx",
+    id: 0xF065,
+    mask: 0xF0FF,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, quirks| {
+      let x = (opcode & 0x0F00) >> 8;
+      for i in 0..(x + 1) {
+        registers.set_v(
+          i as usize,
+          mem.read_byte(registers.i.wrapping_add(i) as usize)?
+        )?;
+      }
+      if quirks.memory {
+        registers.i = registers.i.wrapping_add(if quirks.memory_increment_by_x { x } else { x + 1 });
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn scd_nibble() -> Instruction {
+  Instruction {
+    name: "SCD nibble",
+    octo: "scroll-down nibble",
+    id: 0x00C0,
+    mask: 0xFFF0,
+    execute: |opcode, _mem, registers, frame_buffer, _rng, _quirks| {
+      let n = opcode & 0x000F;
+      frame_buffer.scroll_down(n);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn scr() -> Instruction {
+  Instruction {
+    name: "SCR",
+    octo: "scroll-right",
+    id: 0x00FB,
+    mask: 0xFFFF,
+    execute: |_opcode, _mem, registers, frame_buffer, _rng, _quirks| {
+      frame_buffer.scroll_right(4);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn scl() -> Instruction {
+  Instruction {
+    name: "SCL",
+    octo: "scroll-left",
+    id: 0x00FC,
+    mask: 0xFFFF,
+    execute: |_opcode, _mem, registers, frame_buffer, _rng, _quirks| {
+      frame_buffer.scroll_left(4);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn exit() -> Instruction {
+  Instruction {
+    name: "EXIT",
+    octo: "exit",
+    id: 0x00FD,
+    mask: 0xFFFF,
+    execute: |_opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      registers.exited = true;
+      Ok(())
+    }
+  }
+}
+
+const fn low() -> Instruction {
+  Instruction {
+    name: "LOW",
+    octo: "lores",
+    id: 0x00FE,
+    mask: 0xFFFF,
+    execute: |_opcode, _mem, registers, frame_buffer, _rng, _quirks| {
+      frame_buffer.set_hires(false)?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn high() -> Instruction {
+  Instruction {
+    name: "HIGH",
+    octo: "hires",
+    id: 0x00FF,
+    mask: 0xFFFF,
+    execute: |_opcode, _mem, registers, frame_buffer, _rng, _quirks| {
+      frame_buffer.set_hires(true)?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn drw_vx_vy_0() -> Instruction {
+  Instruction {
+    name: "DRW Vx, Vy, 0",
+    octo: "sprite vx vy 0",
+    id: 0xD000,
+    mask: 0xF00F,
+    execute: |opcode, mem, registers, frame_buffer, _rng, quirks| {
+      draw_sprite(mem, registers, frame_buffer, quirks, opcode, 16, 16)
+    }
+  }
+}
+
+const fn ld_hf_vx() -> Instruction {
+  Instruction {
+    name: "LD HF, Vx",
+    octo: "i := bighex vx",
+    id: 0xF030,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      let vx = registers.get_v(x)? as u16;
+      if vx > 0xF {
+        Err(InterpreterError::InvalidKey(vx as usize))
+      } else {
+        registers.i = BIG_FONT_OFFSET as u16 + (vx * 10);
+        registers.pc += 2;
+        Ok(())
+      }
+    }
+  }
+}
+
+const fn ld_r_vx() -> Instruction {
+  Instruction {
+    name: "LD R, Vx",
+    octo: "saveflags vx",
+    id: 0xF075,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      for i in 0..(x + 1) {
+        registers.rpl[i] = registers.get_v(i)?;
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_vx_r() -> Instruction {
+  Instruction {
+    name: "LD Vx, R",
+    octo: "loadflags vx",
+    id: 0xF085,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      for i in 0..(x + 1) {
+        registers.set_v(i, registers.rpl[i])?;
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn scu_nibble() -> Instruction {
+  Instruction {
+    name: "SCU nibble",
+    octo: "scroll-up nibble",
+    id: 0x00D0,
+    mask: 0xFFF0,
+    execute: |opcode, _mem, registers, frame_buffer, _rng, _quirks| {
+      let n = opcode & 0x000F;
+      frame_buffer.scroll_up(n);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+// Vx..Vy, counting down if x is greater than y
+fn register_range(opcode: u16) -> impl Iterator<Item = usize> {
+  let x = ((opcode & 0x0F00) >> 8) as usize;
+  let y = ((opcode & 0x00F0) >> 4) as usize;
+  let len = x.abs_diff(y) + 1;
+  (0..len).map(move |i| if x <= y { x + i } else { x - i })
+}
+
+const fn ld_arr_i_vx_vy() -> Instruction {
+  Instruction {
+    name: "LD [I], Vx-Vy",
+    octo: "save vx - vy",
+    id: 0x5002,
+    mask: 0xF00F,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      for (offset, v) in register_range(opcode).enumerate() {
+        mem.write_byte(registers.i.wrapping_add(offset as u16) as usize, registers.get_v(v)?)?;
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_arr_vx_vy_i() -> Instruction {
+  Instruction {
+    name: "LD Vx-Vy, [I]",
+    octo: "load vx - vy",
+    id: 0x5003,
+    mask: 0xF00F,
+    execute: |opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      for (offset, v) in register_range(opcode).enumerate() {
+        registers.set_v(v, mem.read_byte(registers.i.wrapping_add(offset as u16) as usize)?)?;
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn ld_i_long() -> Instruction {
+  Instruction {
+    name: "LD I, long",
+    octo: "i := long addr",
+    id: 0xF000,
+    mask: 0xFFFF,
+    execute: |_opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let bytes = mem.peek(registers.pc as usize + 2, 2)?;
+      registers.i = ((bytes[0] as u16) << 8) | (bytes[1] as u16);
+      registers.pc += 4;
+      Ok(())
+    }
+  }
+}
+
+const fn plane_n() -> Instruction {
+  Instruction {
+    name: "PLANE n",
+    octo: "plane n",
+    id: 0xF001,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, frame_buffer, _rng, _quirks| {
+      let n = ((opcode & 0x0F00) >> 8) as u8;
+      frame_buffer.set_planes(n);
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn audio() -> Instruction {
+  Instruction {
+    name: "AUDIO",
+    octo: "audio",
+    id: 0xF002,
+    mask: 0xFFFF,
+    execute: |_opcode, mem, registers, _frame_buffer, _rng, _quirks| {
+      let i = registers.i;
+      for (offset, byte) in registers.audio_pattern.iter_mut().enumerate() {
+        *byte = mem.read_byte(i.wrapping_add(offset as u16) as usize)?;
+      }
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+const fn pitch_vx() -> Instruction {
+  Instruction {
+    name: "PITCH Vx",
+    octo: "pitch := vx",
+    id: 0xF03A,
+    mask: 0xF0FF,
+    execute: |opcode, _mem, registers, _frame_buffer, _rng, _quirks| {
+      let x = ((opcode & 0x0F00) >> 8) as usize;
+      registers.pitch = registers.get_v(x)?;
+      registers.pc += 2;
+      Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{
+    frame_buffer::HIRES_PIXELS,
+    memory::{MEMORY_SIZE, XO_MEMORY_SIZE},
+    registers::Chip8Key,
+    rng::Rng,
+  };
+
+  fn deps() -> (Memory<[u8; MEMORY_SIZE]>, Registers, FrameBuffer<[u8; HIRES_PIXELS]>, Rng) {
+    (Memory::default(), Registers::default(), FrameBuffer::default(), Rng::new(0))
+  }
+
+  fn exec(
+    instr: Instruction,
+    opcode: u16,
+    mem: &mut Memory,
+    registers: &mut Registers,
+    frame_buffer: &mut FrameBuffer,
+    rng: &mut Rng,
+  ) {
+    exec_quirks(instr, opcode, mem, registers, frame_buffer, rng, &Quirks::default());
+  }
+
+  fn exec_quirks(
+    instr: Instruction,
+    opcode: u16,
+    mem: &mut Memory,
+    registers: &mut Registers,
+    frame_buffer: &mut FrameBuffer,
+    rng: &mut Rng,
+    quirks: &Quirks,
+  ) {
+    assert!(
+      (instr.execute)(
+        opcode,
+        mem,
+        registers,
+        frame_buffer,
+        rng,
+        quirks,
+      ).is_ok()
+    );
+  }
+
+  fn exec_err(
+    instr: Instruction,
+    opcode: u16,
+    mem: &mut Memory,
+    registers: &mut Registers,
+    frame_buffer: &mut FrameBuffer,
+    rng: &mut Rng,
+  ) {
+    assert!(
+      (instr.execute)(
+        opcode,
+        mem,
+        registers,
+        frame_buffer,
+        rng,
+        &Quirks::default(),
+      ).is_err()
+    );
+  }
+
+  #[test]
+  fn test_disassemble() {
+    let instructions = InstructionSet::default();
+
+    let cls = instructions.disassemble(0x00E0);
+    assert!(cls.is_some());
+    assert_eq!(cls.unwrap().name, "CLS");
+
+    let ret = instructions.disassemble(0x00EE);
+    assert!(ret.is_some());
+    assert_eq!(ret.unwrap().name, "RET");
+
+    let jp_addr = instructions.disassemble(0x1AAA);
+    assert!(jp_addr.is_some());
+    assert_eq!(jp_addr.unwrap().name, "JP addr");
+
+    let call_addr = instructions.disassemble(0x2AAA);
+    assert!(call_addr.is_some());
+    assert_eq!(call_addr.unwrap().name, "CALL addr");
+
+    let se_vx_byte = instructions.disassemble(0x3AAA);
+    assert!(se_vx_byte.is_some());
+    assert_eq!(se_vx_byte.unwrap().name, "SE Vx, byte");
+
+    let sne_vx_byte = instructions.disassemble(0x4AAA);
+    assert!(sne_vx_byte.is_some());
+    assert_eq!(sne_vx_byte.unwrap().name, "SNE Vx, byte");
+
+    let se_vx_vy = instructions.disassemble(0x5AB0);
+    assert!(se_vx_vy.is_some());
+    assert_eq!(se_vx_vy.unwrap().name, "SE Vx, Vy");
+
+    let ld_vx_byte = instructions.disassemble(0x6AAA);
+    assert!(ld_vx_byte.is_some());
+    assert_eq!(ld_vx_byte.unwrap().name, "LD Vx, byte");
+
+    let add_vx_byte = instructions.disassemble(0x7AAA);
+    assert!(add_vx_byte.is_some());
+    assert_eq!(add_vx_byte.unwrap().name, "ADD Vx, byte");
+
+    let ld_vx_vy = instructions.disassemble(0x8AB0);
+    assert!(ld_vx_vy.is_some());
+    assert_eq!(ld_vx_vy.unwrap().name, "LD Vx, Vy");
+
+    let or_vx_vy = instructions.disassemble(0x8AB1);
+    assert!(or_vx_vy.is_some());
+    assert_eq!(or_vx_vy.unwrap().name, "OR Vx, Vy");
+
+    let and_vx_vy = instructions.disassemble(0x8AB2);
+    assert!(and_vx_vy.is_some());
+    assert_eq!(and_vx_vy.unwrap().name, "AND Vx, Vy");
+
+    let xor_vx_vy = instructions.disassemble(0x8AB3);
+    assert!(xor_vx_vy.is_some());
+    assert_eq!(xor_vx_vy.unwrap().name, "XOR Vx, Vy");
+
+    let add_vx_vy = instructions.disassemble(0x8AB4);
+    assert!(add_vx_vy.is_some());
+    assert_eq!(add_vx_vy.unwrap().name, "ADD Vx, Vy");
+
+    let sub_vx_vy = instructions.disassemble(0x8AB5);
+    assert!(sub_vx_vy.is_some());
+    assert_eq!(sub_vx_vy.unwrap().name, "SUB Vx, Vy");
+
+    let shr_vx = instructions.disassemble(0x8AB6);
+    assert!(shr_vx.is_some());
+    assert_eq!(shr_vx.unwrap().name, "SHR Vx");
+
+    let subn_vx_vy = instructions.disassemble(0x8AB7);
+    assert!(subn_vx_vy.is_some());
+    assert_eq!(subn_vx_vy.unwrap().name, "SUBN Vx, Vy");
+
+    let shl_vx = instructions.disassemble(0x8ABE);
+    assert!(shl_vx.is_some());
+    assert_eq!(shl_vx.unwrap().name, "SHL Vx");
+
+    let sne_vx_vy = instructions.disassemble(0x9AB0);
+    assert!(sne_vx_vy.is_some());
+    assert_eq!(sne_vx_vy.unwrap().name, "SNE Vx, Vy");
+
+    let ld_i_addr = instructions.disassemble(0xAEEE);
+    assert!(ld_i_addr.is_some());
+    assert_eq!(ld_i_addr.unwrap().name, "LD I, addr");
+
+    let jp_v0_addr = instructions.disassemble(0xBEEE);
+    assert!(jp_v0_addr.is_some());
+    assert_eq!(jp_v0_addr.unwrap().name, "JP V0, addr");
+
+    let rnd_vx_byte = instructions.disassemble(0xCAEE);
+    assert!(rnd_vx_byte.is_some());
+    assert_eq!(rnd_vx_byte.unwrap().name, "RND Vx, byte");
+
+    let drw_vx_vy_n = instructions.disassemble(0xDABC);
+    assert!(drw_vx_vy_n.is_some());
+    assert_eq!(drw_vx_vy_n.unwrap().name, "DRW Vx, Vy, nibble");
+
+    let skp_vx = instructions.disassemble(0xEA9E);
+    assert!(skp_vx.is_some());
+    assert_eq!(skp_vx.unwrap().name, "SKP Vx");
+
+    let sknp_vx = instructions.disassemble(0xEAA1);
+    assert!(sknp_vx.is_some());
+    assert_eq!(sknp_vx.unwrap().name, "SKNP Vx");
+
+    let ld_vx_dt = instructions.disassemble(0xFA07);
+    assert!(ld_vx_dt.is_some());
+    assert_eq!(ld_vx_dt.unwrap().name, "LD Vx, DT");
+
+    let ld_vx_k = instructions.disassemble(0xFA0A);
+    assert!(ld_vx_k.is_some());
+    assert_eq!(ld_vx_k.unwrap().name, "LD Vx, K");
+
+    let ld_dt_vx = instructions.disassemble(0xFA15);
+    assert!(ld_dt_vx.is_some());
+    assert_eq!(ld_dt_vx.unwrap().name, "LD DT, Vx");
+
+    let ld_st_vx = instructions.disassemble(0xFA18);
+    assert!(ld_st_vx.is_some());
+    assert_eq!(ld_st_vx.unwrap().name, "LD ST, Vx");
+
+    let add_i_vx = instructions.disassemble(0xFA1E);
+    assert!(add_i_vx.is_some());
+    assert_eq!(add_i_vx.unwrap().name, "ADD I, Vx");
+
+    let ld_f_vx = instructions.disassemble(0xFA29);
+    assert!(ld_f_vx.is_some());
+    assert_eq!(ld_f_vx.unwrap().name, "LD F, Vx");
+
+    let ld_b_vx = instructions.disassemble(0xFA33);
+    assert!(ld_b_vx.is_some());
+    assert_eq!(ld_b_vx.unwrap().name, "LD B, Vx");
+
+    let ld_arr_i_vx = instructions.disassemble(0xFA55);
+    assert!(ld_arr_i_vx.is_some());
+    assert_eq!(ld_arr_i_vx.unwrap().name, "LD [I], Vx");
+
+    let ld_arr_vx_i = instructions.disassemble(0xFA65);
+    assert!(ld_arr_vx_i.is_some());
+    assert_eq!(ld_arr_vx_i.unwrap().name, "LD Vx, [I]");
+  }
+
+  #[test]
+  fn test_cls() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    exec(cls(), 0x00E0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(frame_buffer.frame(), &[0; 2048]);
+  }
+
+  #[test]
+  fn test_ret() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.push(0xF0).unwrap();
+    exec(ret(), 0x00EE, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF2);
+    // TODO: Assert on error type
+    exec_err(ret(), 0x00EE, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+  }
+
+  #[test]
+  fn test_jp_addr() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    exec(jp_addr(), 0x10F0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0x0F0);
+  }
+
+  #[test]
+  fn test_call_addr() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    let pc = registers.pc;
+    exec(call_addr(), 0x20F0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.stack(), &[pc]);
+    assert_eq!(registers.pc, 0x0F0);
+    registers.sp = 16;
+    exec_err(call_addr(), 0x20F0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+  }
+
+  #[test]
+  fn test_se_vx_byte() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xFF0;
+    registers.set_v(0, 0xF0).unwrap();
+    exec(se_vx_byte(), 0x30F0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xFF4);
+    registers.pc = 0xFF0;
+    registers.set_v(0, 0xFF).unwrap();
+    exec(se_vx_byte(), 0x30F0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xFF2);
+  }
+
+  #[test]
+  fn test_sne_vx_byte() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xFF0;
+    registers.set_v(0, 0xF0).unwrap();
+    exec(sne_vx_byte(), 0x40F0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xFF2);
+    registers.pc = 0xFF0;
+    registers.set_v(0, 0xFF).unwrap();
+    exec(sne_vx_byte(), 0x40F0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xFF4);
+  }
+
+  #[test]
+  fn test_se_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xFF0;
+    registers.set_v(0, 0xF0).unwrap();
+    registers.set_v(1, 0xF0).unwrap();
+    exec(se_vx_vy(), 0x5010, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xFF4);
+    registers.pc = 0xFF0;
+    registers.set_v(0, 0xF0).unwrap();
+    registers.set_v(1, 0xF1).unwrap();
+    exec(se_vx_vy(), 0x5010, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xFF2);
+  }
+
+  #[test]
+  fn test_ld_vx_byte() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    exec(ld_vx_byte(), 0x60F0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0xF0);
+  }
+
+  #[test]
+  fn test_add_vx_byte() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x02).unwrap();
+    exec(add_vx_byte(), 0x7002, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0x04);
+  }
+
+  #[test]
+  fn test_ld_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x01).unwrap();
+    registers.set_v(1, 0x02).unwrap();
+    exec(ld_vx_vy(), 0x8010, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0x02);
+  }
+  
+  #[test]
+  fn test_or_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x3).unwrap();
+    registers.set_v(1, 0x4).unwrap();
+    exec(or_vx_vy(), 0x8011, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0x7);
+  }
+  
+  #[test]
+  fn test_and_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x34).unwrap();
+    registers.set_v(1, 0xF0).unwrap();
+    exec(and_vx_vy(), 0x8012, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0x30);
+  }
+  
+  #[test]
+  fn test_xor_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x3).unwrap();
+    registers.set_v(1, 0x3).unwrap();
+    exec(xor_vx_vy(), 0x8013, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0);
+  }
+
+  #[test]
+  fn test_logic_vf_reset_quirk() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    for (instr, opcode) in [(or_vx_vy as fn() -> Instruction, 0x8011), (and_vx_vy, 0x8012), (xor_vx_vy, 0x8013)] {
+      registers.set_vf(0xAA);
+      exec_quirks(instr(), opcode, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+      assert_eq!(registers.get_vf(), 0);
+      registers.set_vf(0xAA);
+      exec_quirks(instr(), opcode, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::schip_modern());
+      assert_eq!(registers.get_vf(), 0xAA);
+    }
+  }
+
+  #[test]
+  fn test_logic_vf_reset_quirk_vf_operand() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    // When VF is the destination, the reset wins over the result
+    registers.set_v(0, 0x0F).unwrap();
+    registers.set_vf(0xF0);
+    exec_quirks(or_vx_vy(), 0x8F01, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert_eq!(registers.get_vf(), 0);
+    registers.set_vf(0xF0);
+    exec_quirks(or_vx_vy(), 0x8F01, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::schip_modern());
+    assert_eq!(registers.get_vf(), 0xFF);
+  }
+  
+  #[test]
+  fn test_add_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x3).unwrap();
+    registers.set_v(1, 0x4).unwrap();
+    exec(add_vx_vy(), 0x8014, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0x7);
+    assert_eq!(registers.get_vf(), 0);
+    registers.set_v(0, 0xFF).unwrap();
+    registers.set_v(1, 0xFF).unwrap();
+    exec(add_vx_vy(), 0x8014, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0xFE);
+    assert_eq!(registers.get_vf(), 1);
+  }
+  
+  #[test]
+  fn test_sub_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x4).unwrap();
+    registers.set_v(1, 0x2).unwrap();
+    exec(sub_vx_vy(), 0x8015, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0x2);
+    assert_eq!(registers.get_vf(), 1);
+    registers.set_v(0, 0x2).unwrap();
+    registers.set_v(1, 0x3).unwrap();
+    exec(sub_vx_vy(), 0x8015, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0xFF);
+    assert_eq!(registers.get_vf(), 0);
+    registers.set_v(0, 0x3).unwrap();
+    exec(sub_vx_vy(), 0x8015, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0);
+    assert_eq!(registers.get_vf(), 1);
+  }
+
+  #[test]
+  fn test_shr_vx() {  
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x3).unwrap();
+    exec(shr_vx(), 0x8006, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 1);
+    assert_eq!(registers.get_vf(), 1);
+  }
+
+  #[test]
+  fn test_shr_vx_quirks() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x8).unwrap();
+    registers.set_v(1, 0x3).unwrap();
+    exec_quirks(shr_vx(), 0x8016, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert_eq!(registers.get_v(0).unwrap(), 1);
+    assert_eq!(registers.get_vf(), 1);
+    registers.set_v(0, 0x8).unwrap();
+    exec_quirks(shr_vx(), 0x8016, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::schip_modern());
+    assert_eq!(registers.get_v(0).unwrap(), 4);
+    assert_eq!(registers.get_vf(), 0);
+  }
+  
+  #[test]
+  fn test_subn_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x3).unwrap();
+    registers.set_v(1, 0x2).unwrap();
+    exec(subn_vx_vy(), 0x8017, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 255);
+    assert_eq!(registers.get_vf(), 0);
+    registers.set_v(0, 0x2).unwrap();
+    registers.set_v(1, 0x3).unwrap();
+    exec(subn_vx_vy(), 0x8017, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 1);
+    assert_eq!(registers.get_vf(), 1);
+    registers.set_v(0, 0x3).unwrap();
+    exec(subn_vx_vy(), 0x8017, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 0);
+    assert_eq!(registers.get_vf(), 1);
+  }
+
+  #[test]
+  fn test_shl_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x3).unwrap();
+    exec(shl_vx(), 0x800E, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 6);
+    assert_eq!(registers.get_vf(), 0);
+  }
+
+  #[test]
+  fn test_shl_vx_quirks() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x1).unwrap();
+    registers.set_v(1, 0x81).unwrap();
+    exec_quirks(shl_vx(), 0x801E, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert_eq!(registers.get_v(0).unwrap(), 2);
+    assert_eq!(registers.get_vf(), 1);
+    registers.set_v(0, 0x1).unwrap();
+    exec_quirks(shl_vx(), 0x801E, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::schip_modern());
+    assert_eq!(registers.get_v(0).unwrap(), 2);
+    assert_eq!(registers.get_vf(), 0);
+  }
+
+  #[test]
+  fn test_sne_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xF0;
+    registers.set_v(0, 1).unwrap();
+    registers.set_v(1, 1).unwrap();
+    exec(sne_vx_vy(), 0x9010, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF2);
+    registers.pc = 0xF0;
+    registers.set_v(0, 1).unwrap();
+    registers.set_v(1, 2).unwrap();
+    exec(sne_vx_vy(), 0x9010, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF4);
+  }
+
+  #[test]
+  fn test_ld_i_addr() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    exec(ld_i_addr(), 0xAF00, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.i, 0xF00);
+  }
+
+  #[test]
+  fn test_jp_v0_addr() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x010).unwrap();
+    exec(jp_v0_addr(), 0xB010, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 32);
+  }
+
+  #[test]
+  fn test_jp_v0_addr_quirks() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 0x01).unwrap();
+    registers.set_v(3, 0x10).unwrap();
+    exec_quirks(jp_v0_addr(), 0xB300, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert_eq!(registers.pc, 0x301);
+    exec_quirks(jp_v0_addr(), 0xB300, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::schip_modern());
+    assert_eq!(registers.pc, 0x310);
+  }
+
+  #[test]
+  fn test_drw_vx_vy_nibble() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    mem.write(0x300, &[0xC0, 0x80]).unwrap();
+    registers.set_v(0, 2).unwrap();
+    registers.set_v(1, 3).unwrap();
+    exec(drw_vx_vy_nibble(), 0xD012, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(frame_buffer.get_xy(2, 3).unwrap());
+    assert!(frame_buffer.get_xy(3, 3).unwrap());
+    assert!(frame_buffer.get_xy(2, 4).unwrap());
+    assert!(!frame_buffer.get_xy(3, 4).unwrap());
+    assert_eq!(registers.get_vf(), 0);
+    // Drawing the same sprite again erases it and reports the collision
+    exec(drw_vx_vy_nibble(), 0xD012, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(frame_buffer.frame().iter().all(|&pixel| pixel == 0));
+    assert_eq!(registers.get_vf(), 1);
+  }
+
+  #[test]
+  fn test_drw_wraps() {
+    let (_, mut registers, mut frame_buffer, mut rng) = deps();
+    let mut mem: Memory<[u8; XO_MEMORY_SIZE]> = Memory::new(XO_MEMORY_SIZE);
+    registers.i = 0xFFFF;
+    mem.write(0xFFFF, &[0x80]).unwrap();
+    mem.write(0, &[0x40]).unwrap();
+    exec(drw_vx_vy_nibble(), 0xD012, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(frame_buffer.get_xy(0, 0).unwrap());
+    assert!(frame_buffer.get_xy(1, 1).unwrap());
+  }
+
+  #[test]
+  fn test_drw_vx_vy_nibble_quirks() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    mem.write(0x300, &[0xC0, 0xC0]).unwrap();
+    registers.set_v(0, 63).unwrap();
+    registers.set_v(1, 31).unwrap();
+    exec_quirks(drw_vx_vy_nibble(), 0xD012, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert!(frame_buffer.get_xy(63, 31).unwrap());
+    assert!(!frame_buffer.get_xy(0, 31).unwrap());
+    assert!(!frame_buffer.get_xy(63, 0).unwrap());
+    frame_buffer.clear();
+    exec_quirks(drw_vx_vy_nibble(), 0xD012, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::xo_chip());
+    assert!(frame_buffer.get_xy(63, 31).unwrap());
+    assert!(frame_buffer.get_xy(0, 31).unwrap());
+    assert!(frame_buffer.get_xy(63, 0).unwrap());
+    assert!(frame_buffer.get_xy(0, 0).unwrap());
+  }
+
+  #[test]
+  fn test_rnd_vx_byte() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    let mut expected = rng;
+    exec(rnd_vx_vyte(), 0xC00F, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), expected.next_u8() & 0x0F);
+    assert_eq!(registers.pc, 0x202);
+  }
+
+  #[test]
+  fn test_skp_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xF0;
+    registers.keys[4] = true;
+    registers.set_v(0, 4).unwrap();
+    exec(skp_vx(), 0xE09E, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF4);
+    registers.pc = 0xF0;
+    exec(skp_vx(), 0xE19E, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF2);
+  }
+
+  #[test]
+  fn test_sknp_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xF0;
+    registers.keys[4] = true;
+    registers.set_v(0, 4).unwrap();
+    exec(sknp_vx(), 0xE0A1, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF2);
+    registers.pc = 0xF0;
+    exec(sknp_vx(), 0xE1A1, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF4);
+  }
+
+  #[test]
+  fn test_ld_vx_dt() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_dt(10);
+    exec(ld_vx_dt(), 0xF407, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(4).unwrap(), 10);
+  }
+
+  #[test]
+  fn test_ld_vx_k() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    let quirks = Quirks { key_release: false, ..Quirks::default() };
+    registers.pc = 0xF0;
+    exec_quirks(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &quirks);
+    assert_eq!(registers.get_v(0).unwrap(), 0);
+    assert_eq!(registers.pc, 0xF0);
+    registers.keys[4] = true;
+    exec_quirks(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &quirks);
+    assert_eq!(registers.get_v(0).unwrap(), 4);
+    assert_eq!(registers.pc, 0xF2);
+  }
+
+  #[test]
+  fn test_ld_vx_k_release() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xF0;
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF0);
+    registers.set_key(Chip8Key::Q, true);
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    // Still held, keep waiting
+    assert_eq!(registers.get_v(0).unwrap(), 0);
+    assert_eq!(registers.pc, 0xF0);
+    registers.set_key(Chip8Key::Q, false);
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 4);
+    assert_eq!(registers.pc, 0xF2);
+  }
+
+  #[test]
+  fn test_ld_vx_k_release_edge() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xF0;
+    // A release that happened before the wait started doesn't count
+    registers.set_key(Chip8Key::W, true);
+    registers.set_key(Chip8Key::W, false);
+    registers.set_key(Chip8Key::E, true);
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0xF0);
+    // A release and re-press between two polls still completes the wait
+    registers.set_key(Chip8Key::E, false);
+    registers.set_key(Chip8Key::E, true);
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 6);
+    assert_eq!(registers.pc, 0xF2);
+  }
+
+  #[test]
+  fn test_ld_vx_k_tap() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.pc = 0xF0;
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.key_wait, Some(KeyWait::Press));
+    // Pressed and released before the next cycle, so it's never seen held
+    registers.set_key(Chip8Key::A, true);
+    registers.set_key(Chip8Key::A, false);
+    exec(ld_vx_k(), 0xF00A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 7);
+    assert_eq!(registers.pc, 0xF2);
+    assert_eq!(registers.key_wait, None);
+  }
+
+  #[test]
+  fn test_ld_dt_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 4).unwrap();
+    exec(ld_dt_vx(), 0xF015, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_dt(), 4);
+  }
+
+  #[test]
+  fn test_ld_st_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 4).unwrap();
+    exec(ld_st_vx(), 0xF018, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_st(), 4);
+  }
+
+  #[test]
+  fn test_add_i_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 5;
+    registers.set_v(2, 4).unwrap();
+    exec(add_i_vx(), 0xF21E, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.i, 9);
+  }
+
+  #[test]
+  fn test_ld_f_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 5).unwrap();
+    exec(ld_f_vx(), 0xF029, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.i, FONT_OFFSET as u16 + 25);
+    registers.set_v(0, 200).unwrap();
+    exec_err(ld_f_vx(), 0xF029, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+  }
+
+  #[test]
+  fn test_ld_b_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    registers.set_v(0, 0x7B).unwrap();
+    exec(ld_b_vx(), 0xF033, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(mem.read_byte(0x300).unwrap(), 1);
+    assert_eq!(mem.read_byte(0x301).unwrap(), 2);
+    assert_eq!(mem.read_byte(0x302).unwrap(), 3);
+  }
+
+  #[test]
+  fn test_ld_b_vx_wraps() {
+    let (_, mut registers, mut frame_buffer, mut rng) = deps();
+    let mut mem: Memory<[u8; XO_MEMORY_SIZE]> = Memory::new(XO_MEMORY_SIZE);
+    registers.i = 0xFFFF;
+    registers.set_v(0, 0x7B).unwrap();
+    exec(ld_b_vx(), 0xF033, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(mem.read_byte(0xFFFF).unwrap(), 1);
+    assert_eq!(mem.read(0, 2).unwrap(), &[2, 3]);
+  }
+
+  #[test]
+  fn test_ld_arr_i_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    registers.set_v(0, 1).unwrap();
+    registers.set_v(1, 2).unwrap();
+    registers.set_v(2, 3).unwrap();
+    exec(ld_arr_i_vx(), 0xF255, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(mem.read_byte(0x300).unwrap(), 1);
+    assert_eq!(mem.read_byte(0x301).unwrap(), 2);
+    assert_eq!(mem.read_byte(0x302).unwrap(), 3);
+  }
+
+  #[test]
+  fn test_ld_arr_quirks() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    exec_quirks(ld_arr_i_vx(), 0xF255, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert_eq!(registers.i, 0x303);
+    exec_quirks(ld_arr_vx_i(), 0xF265, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert_eq!(registers.i, 0x306);
+    registers.i = 0x300;
+    exec_quirks(ld_arr_i_vx(), 0xF255, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::schip_modern());
+    assert_eq!(registers.i, 0x300);
+    exec_quirks(ld_arr_vx_i(), 0xF265, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::schip_modern());
+    assert_eq!(registers.i, 0x300);
+    let chip48 = Quirks { memory_increment_by_x: true, ..Quirks::vip() };
+    exec_quirks(ld_arr_i_vx(), 0xF255, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &chip48);
+    assert_eq!(registers.i, 0x302);
+    exec_quirks(ld_arr_vx_i(), 0xF265, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &chip48);
+    assert_eq!(registers.i, 0x304);
+  }
+
+  #[test]
+  fn test_ld_arr_vx_i() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    mem.write(0x300, &[1, 2, 3]).unwrap();
+    exec(ld_arr_vx_i(), 0xF265, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.get_v(0).unwrap(), 1);
+    assert_eq!(registers.get_v(1).unwrap(), 2);
+    assert_eq!(registers.get_v(2).unwrap(), 3);
+  }
+
+  #[test]
+  fn test_i_wraps() {
+    // XO-CHIP can point I at the very end of memory, where it wraps back to 0
+    let (_, mut registers, mut frame_buffer, mut rng) = deps();
+    let mut mem: Memory<[u8; XO_MEMORY_SIZE]> = Memory::new(XO_MEMORY_SIZE);
+    registers.i = 0xFFFF;
+    registers.set_v(2, 2).unwrap();
+    exec(add_i_vx(), 0xF21E, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.i, 1);
+
+    registers.i = 0xFFFF;
+    registers.set_v(0, 0xAA).unwrap();
+    registers.set_v(1, 0xBB).unwrap();
+    exec_quirks(ld_arr_i_vx(), 0xF155, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert_eq!(mem.read(0xFFFF, 1).unwrap(), &[0xAA]);
+    assert_eq!(mem.read(0, 1).unwrap(), &[0xBB]);
+    assert_eq!(registers.i, 1);
+
+    registers.i = 0xFFFF;
+    exec_quirks(ld_arr_vx_i(), 0xF265, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::vip());
+    assert_eq!(registers.v[..3], [0xAA, 0xBB, 0]);
+    assert_eq!(registers.i, 2);
+  }
+
+  #[test]
+  fn test_disassemble_schip() {
+    let chip8 = InstructionSet::new(Variant::Chip8);
+    let schip = InstructionSet::new(Variant::SuperChip);
+
+    assert!(chip8.disassemble(0x00FF).is_none());
+    assert_eq!(schip.disassemble(0x00C4).unwrap().name, "SCD nibble");
+    assert_eq!(schip.disassemble(0x00FB).unwrap().name, "SCR");
+    assert_eq!(schip.disassemble(0x00FC).unwrap().name, "SCL");
+    assert_eq!(schip.disassemble(0x00FD).unwrap().name, "EXIT");
+    assert_eq!(schip.disassemble(0x00FE).unwrap().name, "LOW");
+    assert_eq!(schip.disassemble(0x00FF).unwrap().name, "HIGH");
+    assert_eq!(chip8.disassemble(0xDAB0).unwrap().name, "DRW Vx, Vy, nibble");
+    assert_eq!(schip.disassemble(0xDAB0).unwrap().name, "DRW Vx, Vy, 0");
+    assert_eq!(schip.disassemble(0xDAB5).unwrap().name, "DRW Vx, Vy, nibble");
+    assert_eq!(schip.disassemble(0xFA30).unwrap().name, "LD HF, Vx");
+    assert_eq!(schip.disassemble(0xFA75).unwrap().name, "LD R, Vx");
+    assert_eq!(schip.disassemble(0xFA85).unwrap().name, "LD Vx, R");
+  }
+
+  #[test]
+  fn test_scroll() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    frame_buffer.set_xy(8, 8, true).unwrap();
+    exec(scd_nibble(), 0x00C3, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(frame_buffer.get_xy(8, 11).unwrap());
+    exec(scr(), 0x00FB, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(frame_buffer.get_xy(12, 11).unwrap());
+    exec(scl(), 0x00FC, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(frame_buffer.get_xy(8, 11).unwrap());
+    assert_eq!(registers.pc, 0x206);
+  }
+
+  #[test]
+  fn test_exit() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    exec(exit(), 0x00FD, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(registers.exited);
+    assert_eq!(registers.pc, 0x200);
+  }
+
+  #[test]
+  fn test_low_high() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    exec(high(), 0x00FF, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(frame_buffer.hires());
+    assert_eq!(frame_buffer.frame().len(), 128 * 64);
+    exec(low(), 0x00FE, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert!(!frame_buffer.hires());
+    assert_eq!(frame_buffer.frame().len(), 64 * 32);
+  }
+
+  #[test]
+  fn test_drw_vx_vy_0() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    frame_buffer.set_hires(true).unwrap();
+    registers.i = 0x300;
+    let mut sprite = [0; 32];
+    sprite[0] = 0x80;
+    sprite[31] = 0x01;
+    mem.write(0x300, &sprite).unwrap();
+    registers.set_v(0, 100).unwrap();
+    registers.set_v(1, 40).unwrap();
+    exec_quirks(drw_vx_vy_0(), 0xD010, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::schip_modern());
+    assert!(frame_buffer.get_xy(100, 40).unwrap());
+    assert!(frame_buffer.get_xy(115, 55).unwrap());
+    assert_eq!(frame_buffer.frame().iter().filter(|&&pixel| pixel == 1).count(), 2);
+    assert_eq!(registers.get_vf(), 0);
+    assert!(!registers.display_wait);
+  }
+
+  #[test]
+  fn test_ld_hf_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 2).unwrap();
+    exec(ld_hf_vx(), 0xF030, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.i, BIG_FONT_OFFSET as u16 + 20);
+    assert_eq!(mem.read(registers.i as usize, 2).unwrap(), &[0xFF, 0xFF]);
+    registers.set_v(0, 16).unwrap();
+    exec_err(ld_hf_vx(), 0xF030, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+  }
+
+  #[test]
+  fn test_ld_r_vx() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.set_v(0, 1).unwrap();
+    registers.set_v(1, 2).unwrap();
+    registers.set_v(2, 3).unwrap();
+    exec(ld_r_vx(), 0xF175, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(&registers.rpl[..3], &[1, 2, 0]);
+    registers.v = [0; 16];
+    exec(ld_vx_r(), 0xF185, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(&registers.v[..3], &[1, 2, 0]);
+  }
+
+  #[test]
+  fn test_disassemble_xo_chip() {
+    let schip = InstructionSet::new(Variant::SuperChip);
+    let xo_chip = InstructionSet::new(Variant::XoChip);
+
+    assert!(schip.disassemble(0xF000).is_none());
+    assert_eq!(xo_chip.disassemble(0x00D4).unwrap().name, "SCU nibble");
+    assert_eq!(xo_chip.disassemble(0x5AB2).unwrap().name, "LD [I], Vx-Vy");
+    assert_eq!(xo_chip.disassemble(0x5AB3).unwrap().name, "LD Vx-Vy, [I]");
+    assert_eq!(xo_chip.disassemble(0xF000).unwrap().name, "LD I, long");
+    assert_eq!(xo_chip.disassemble(0xF201).unwrap().name, "PLANE n");
+    assert_eq!(xo_chip.disassemble(0xF002).unwrap().name, "AUDIO");
+    assert_eq!(xo_chip.disassemble(0xFA3A).unwrap().name, "PITCH Vx");
+    // Instructions from the earlier sets are still there
+    assert_eq!(xo_chip.disassemble(0x00FF).unwrap().name, "HIGH");
+    assert_eq!(xo_chip.disassemble(0x5AB0).unwrap().name, "SE Vx, Vy");
+  }
+
+  #[test]
+  fn test_skip_long_instruction() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    mem.write(0x202, &[0xF0, 0x00, 0x12, 0x34]).unwrap();
+    exec(se_vx_byte(), 0x3000, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pc, 0x206);
+  }
+
+  #[test]
+  fn test_ld_i_long() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    mem.write(0x200, &[0xF0, 0x00, 0xAB, 0xCD]).unwrap();
+    exec(ld_i_long(), 0xF000, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.i, 0xABCD);
+    assert_eq!(registers.pc, 0x204);
+  }
+
+  #[test]
+  fn test_ld_arr_vx_vy() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    registers.v[2] = 1;
+    registers.v[3] = 2;
+    registers.v[4] = 3;
+    exec(ld_arr_i_vx_vy(), 0x5242, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(mem.read(0x300, 3).unwrap(), &[1, 2, 3]);
+    assert_eq!(registers.i, 0x300);
+    // Reversed ranges load in descending order
+    exec(ld_arr_vx_vy_i(), 0x5753, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(&registers.v[5..8], &[3, 2, 1]);
+    assert_eq!(registers.i, 0x300);
+  }
+
+  #[test]
+  fn test_ld_arr_i_vx_vy_wraps() {
+    let (_, mut registers, mut frame_buffer, mut rng) = deps();
+    let mut mem: Memory<[u8; XO_MEMORY_SIZE]> = Memory::new(XO_MEMORY_SIZE);
+    registers.i = 0xFFFF;
+    registers.v[2] = 1;
+    registers.v[3] = 2;
+    exec(ld_arr_i_vx_vy(), 0x5232, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(mem.read_byte(0xFFFF).unwrap(), 1);
+    assert_eq!(mem.read_byte(0).unwrap(), 2);
+    assert_eq!(registers.i, 0xFFFF);
+  }
+
+  #[test]
+  fn test_ld_arr_vx_vy_i_wraps() {
+    let (_, mut registers, mut frame_buffer, mut rng) = deps();
+    let mut mem: Memory<[u8; XO_MEMORY_SIZE]> = Memory::new(XO_MEMORY_SIZE);
+    registers.i = 0xFFFF;
+    mem.write(0xFFFF, &[1]).unwrap();
+    mem.write(0, &[2]).unwrap();
+    exec(ld_arr_vx_vy_i(), 0x5233, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(&registers.v[2..4], &[1, 2]);
+    assert_eq!(registers.i, 0xFFFF);
+  }
+
+  #[test]
+  fn test_plane_n() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    mem.write(0x300, &[0x80, 0xC0]).unwrap();
+    exec(plane_n(), 0xF301, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(frame_buffer.planes(), 3);
+    exec_quirks(drw_vx_vy_nibble(), 0xD001, &mut mem, &mut registers, &mut frame_buffer, &mut rng, &Quirks::xo_chip());
+    // Plane 1 gets the first byte, plane 2 the second
+    assert_eq!(&frame_buffer.frame()[0..2], &[3, 2]);
+    exec(plane_n(), 0xF201, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    exec(cls(), 0x00E0, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(&frame_buffer.frame()[0..2], &[1, 0]);
+  }
+
+  #[test]
+  fn test_audio_pitch() {
+    let (mut mem, mut registers, mut frame_buffer, mut rng) = deps();
+    registers.i = 0x300;
+    mem.write(0x300, &[0xAA; 16]).unwrap();
+    exec(audio(), 0xF002, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.audio_pattern, [0xAA; 16]);
+    registers.set_v(1, 100).unwrap();
+    exec(pitch_vx(), 0xF13A, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.pitch, 100);
+  }
+
+  #[test]
+  fn test_audio_wraps() {
+    let (_, mut registers, mut frame_buffer, mut rng) = deps();
+    let mut mem: Memory<[u8; XO_MEMORY_SIZE]> = Memory::new(XO_MEMORY_SIZE);
+    registers.i = 0xFFFF;
+    mem.write(0xFFFF, &[0xAA]).unwrap();
+    mem.write(0, &[0xBB; 15]).unwrap();
+    exec(audio(), 0xF002, &mut mem, &mut registers, &mut frame_buffer, &mut rng);
+    assert_eq!(registers.audio_pattern[0], 0xAA);
+    assert_eq!(registers.audio_pattern[1..], [0xBB; 15]);
+  }
+}
@@ -0,0 +1,493 @@
+//! CHIP-8, SUPER-CHIP and XO-CHIP interpreter core. Frontends drive a
+//! [`Chip8`] one 60 Hz frame (or one cycle) at a time and read the display
+//! back from it; this crate doesn't depend on any windowing or audio output
+//! libraries.
+//!
+//! The core builds under `no_std`. The `alloc` feature adds save states and
+//! rewinding, and `std` (on by default) adds audio rendering and random seeding.
+// Tests always link std, so they can run under any set of features
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+mod assembler;
+#[cfg(feature = "std")]
+pub mod audio;
+mod clock;
+#[cfg(feature = "alloc")]
+mod debugger;
+mod disassembler;
+pub mod error;
+mod frame_buffer;
+mod instructions;
+mod memory;
+mod quirks;
+mod registers;
+#[cfg(feature = "alloc")]
+mod rewind;
+mod rng;
+#[cfg(feature = "alloc")]
+mod state;
+mod variant;
+
+use self::clock::FrameClock;
+use core::time::Duration;
+
+#[cfg(feature = "alloc")]
+pub use self::{
+  assembler::{assemble, assemble_with_symbols, AssemblerError, Symbols},
+  debugger::{Breakpoint, Condition, ConditionError, Debugger, Stop},
+  rewind::Rewinder,
+};
+pub use self::{
+  clock::FRAMES_PER_SECOND,
+  disassembler::{Disassembler, Line, Syntax, Text},
+  error::{InterpreterError, InterpretterResult},
+  frame_buffer::{
+    FrameBuffer, HIRES_HEIGHT, HIRES_PIXELS, HIRES_WIDTH, LORES_HEIGHT, LORES_PIXELS, LORES_WIDTH,
+  },
+  instructions::{ExecuteFn, Instruction, InstructionSet},
+  memory::{
+    Access, Memory, WatchHit, Watchpoint, MAX_WATCHPOINTS, MEMORY_SIZE, ROM_OFFSET, XO_MEMORY_SIZE,
+  },
+  quirks::Quirks,
+  registers::{Chip8Key, KeyWait, Registers},
+  rng::{RandomSource, Rng},
+  variant::Variant,
+};
+
+// Default speed, roughly that of the original COSMAC VIP interpreter
+pub const INSTRUCTIONS_PER_SECOND: usize = 700;
+
+// `MEMORY` and `PIXELS` are the memory and display capacities. The defaults
+// fit every variant; a plain CHIP-8 on a small target can get by with
+// `Chip8<R, MEMORY_SIZE, LORES_PIXELS>`. A variant that needs more than that
+// still runs, but its extra memory and HIGH fail as invalid accesses.
+pub struct Chip8<R = Rng, const MEMORY: usize = XO_MEMORY_SIZE, const PIXELS: usize = HIRES_PIXELS> {
+  memory: Memory<[u8; MEMORY]>,
+  registers: Registers,
+  frame_buffer: FrameBuffer<[u8; PIXELS]>,
+  instructions: InstructionSet,
+  variant: Variant,
+  quirks: Quirks,
+  clock: FrameClock,
+  cycles_per_frame: usize,
+  // Cycles already run in the current frame
+  frame_cycle: usize,
+  frame_count: u64,
+  rng: R,
+}
+
+impl Default for Chip8 {
+  fn default() -> Self {
+    Self::new(Quirks::default())
+  }
+}
+
+#[cfg(feature = "std")]
+fn default_seed() -> u64 {
+  rand::random()
+}
+
+// There's no entropy source to draw on without std, so every machine starts
+// from the same seed unless it's given one with `with_seed` or `with_rng`
+#[cfg(not(feature = "std"))]
+fn default_seed() -> u64 {
+  0
+}
+
+impl Chip8 {
+  pub fn new(quirks: Quirks) -> Self {
+    Self::with_variant(Variant::default(), quirks)
+  }
+
+  pub fn with_seed(seed: u64) -> Self {
+    let mut chip8 = Self::default();
+    chip8.set_seed(seed);
+    chip8
+  }
+
+  pub fn with_variant(variant: Variant, quirks: Quirks) -> Self {
+    Self::with_rng(variant, quirks, Rng::new(default_seed()))
+  }
+
+  pub fn set_seed(&mut self, seed: u64) {
+    self.rng = Rng::new(seed);
+  }
+
+  fn cycles_for_speed(instructions_per_second: usize) -> usize {
+    let fps = FRAMES_PER_SECOND as usize;
+    ((instructions_per_second + fps / 2) / fps).max(1)
+  }
+}
+
+impl<R: RandomSource> Chip8<R> {
+  pub fn with_rng(variant: Variant, quirks: Quirks, rng: R) -> Self {
+    Self::with_capacity(variant, quirks, rng)
+  }
+}
+
+impl<R: RandomSource, const MEMORY: usize, const PIXELS: usize> Chip8<R, MEMORY, PIXELS> {
+  // Like `with_rng`, for machines with other than the default capacities
+  pub fn with_capacity(variant: Variant, quirks: Quirks, rng: R) -> Self {
+    Self {
+      memory: Memory::new(variant.memory_size()),
+      registers: Registers::default(),
+      instructions: InstructionSet::new(variant),
+      variant,
+      frame_buffer: FrameBuffer::default(),
+      quirks,
+      clock: FrameClock::default(),
+      cycles_per_frame: Chip8::cycles_for_speed(INSTRUCTIONS_PER_SECOND),
+      frame_cycle: 0,
+      frame_count: 0,
+      rng,
+    }
+  }
+
+  pub fn load_rom(&mut self, rom: &[u8]) -> InterpretterResult {
+    self.memory.load_rom(rom)?;
+    Ok(())
+  }
+
+  pub fn rng(&self) -> &R {
+    &self.rng
+  }
+
+  pub fn cycles_per_frame(&self) -> usize {
+    self.cycles_per_frame
+  }
+
+  pub fn set_cycles_per_frame(&mut self, cycles: usize) {
+    self.cycles_per_frame = cycles.max(1);
+  }
+
+  // Rounded to a whole number of instructions per frame
+  pub fn set_instructions_per_second(&mut self, instructions_per_second: usize) {
+    self.set_cycles_per_frame(Chip8::cycles_for_speed(instructions_per_second));
+  }
+
+  // Frames run since the machine was created. Not part of save states.
+  pub fn frame_count(&self) -> u64 {
+    self.frame_count
+  }
+
+  pub fn update(&mut self, delta: &Duration) -> InterpretterResult {
+    let frames = self.clock.update(delta);
+    for _ in 0..frames {
+      self.run_frame()?;
+    }
+    Ok(())
+  }
+
+  // Runs the rest of the current 60 Hz frame and returns how many
+  // instructions were executed
+  pub fn run_frame(&mut self) -> InterpretterResult<usize> {
+    let mut executed = 0;
+    loop {
+      if self.step()? {
+        executed += 1;
+      }
+      if self.frame_cycle == 0 {
+        return Ok(executed);
+      }
+    }
+  }
+
+  // Runs a single cycle, for hosts that want to pace execution themselves.
+  // Every `cycles_per_frame` cycles make up a frame. Returns false if the
+  // cycle was idle because the machine has exited or DRW is waiting for the
+  // next frame.
+  pub fn step(&mut self) -> InterpretterResult<bool> {
+    if self.frame_cycle == 0 {
+      // Each frame starts with the vertical blank interrupt, which counts down
+      // the timers and releases any DRW that was waiting on it
+      self.registers.tick_timers();
+      self.registers.display_wait = false;
+      self.frame_count += 1;
+    }
+    self.frame_cycle += 1;
+    if self.frame_cycle >= self.cycles_per_frame {
+      self.frame_cycle = 0;
+    }
+
+    if self.registers.exited || self.registers.display_wait {
+      return Ok(false);
+    }
+    self.instructions.execute(
+      &mut self.memory,
+      &mut self.registers,
+      &mut self.frame_buffer,
+      &mut self.rng,
+      &self.quirks,
+    )?;
+    Ok(true)
+  }
+
+  pub fn memory(&self) -> &Memory {
+    &self.memory
+  }
+
+  // For debuggers, to set watchpoints and poke at memory
+  pub fn memory_mut(&mut self) -> &mut Memory {
+    &mut self.memory
+  }
+
+  pub fn registers(&self) -> &Registers {
+    &self.registers
+  }
+
+  // For debuggers, to change registers by hand
+  pub fn registers_mut(&mut self) -> &mut Registers {
+    &mut self.registers
+  }
+
+  pub fn frame_buffer(&self) -> &FrameBuffer {
+    &self.frame_buffer
+  }
+
+  pub fn frame(&self) -> &[u8] {
+    self.frame_buffer.frame()
+  }
+
+  pub fn resolution(&self) -> (u16, u16) {
+    (self.frame_buffer.width(), self.frame_buffer.height())
+  }
+
+  pub fn exited(&self) -> bool {
+    self.registers.exited
+  }
+
+  pub fn pc(&self) -> u16 {
+    self.registers.pc
+  }
+
+  pub fn i(&self) -> u16 {
+    self.registers.i
+  }
+
+  pub fn v(&self) -> &[u8; 16] {
+    &self.registers.v
+  }
+
+  pub fn stack(&self) -> &[u16] {
+    self.registers.stack()
+  }
+
+  pub fn delay_timer(&self) -> u8 {
+    self.registers.get_dt()
+  }
+
+  pub fn sound_timer(&self) -> u8 {
+    self.registers.get_st()
+  }
+
+  pub fn variant(&self) -> Variant {
+    self.variant
+  }
+
+  pub fn audio_pattern(&self) -> &[u8; 16] {
+    &self.registers.audio_pattern
+  }
+
+  pub fn pitch(&self) -> u8 {
+    self.registers.pitch
+  }
+
+  pub fn quirks(&self) -> &Quirks {
+    &self.quirks
+  }
+
+  pub fn set_quirks(&mut self, quirks: Quirks) {
+    self.quirks = quirks;
+  }
+
+  pub fn set_key(&mut self, key: Chip8Key, pressed: bool) {
+    self.registers.set_key(key, pressed);
+  }
+
+  pub fn sound_active(&self) -> bool {
+    self.registers.get_st() > 0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // LD V0, 0x0A; LD DT, V0; LD ST, V0; JP 0x206
+  const TIMER_ROM: [u8; 8] = [0x60, 0x0A, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06];
+
+  // DRW V0, V0, 1; ADD V1, 0x01; JP 0x200
+  const DRAW_ROM: [u8; 6] = [0xD0, 0x01, 0x71, 0x01, 0x12, 0x00];
+
+  #[test]
+  fn test_update_timers() {
+    let mut chip8 = Chip8::default();
+    chip8.load_rom(&TIMER_ROM).unwrap();
+    // Less than a frame, nothing runs yet
+    chip8.update(&Duration::from_millis(10)).unwrap();
+    assert_eq!(chip8.registers.pc, 0x200);
+    chip8.update(&Duration::from_millis(10)).unwrap();
+    assert_eq!(chip8.registers.get_dt(), 10);
+    assert!(chip8.sound_active());
+    chip8.update(&Duration::from_millis(100)).unwrap();
+    assert_eq!(chip8.registers.get_dt(), 4);
+    assert!(chip8.sound_active());
+    chip8.update(&Duration::from_millis(100)).unwrap();
+    assert_eq!(chip8.registers.get_dt(), 0);
+    assert!(!chip8.sound_active());
+  }
+
+  #[test]
+  fn test_timers_independent_of_instructions() {
+    for cycles in [4, 100] {
+      let mut chip8 = Chip8::default();
+      chip8.set_cycles_per_frame(cycles);
+      chip8.load_rom(&TIMER_ROM).unwrap();
+      for _ in 0..4 {
+        chip8.run_frame().unwrap();
+      }
+      assert_eq!(chip8.registers.get_dt(), 7);
+    }
+  }
+
+  #[test]
+  fn test_cycles_per_frame() {
+    assert_eq!(Chip8::default().cycles_per_frame(), 12);
+    assert_eq!(Chip8::cycles_for_speed(60), 1);
+    assert_eq!(Chip8::cycles_for_speed(1), 1);
+    let mut chip8 = Chip8::default();
+    chip8.set_instructions_per_second(1000);
+    assert_eq!(chip8.cycles_per_frame(), 17);
+  }
+
+  #[test]
+  fn test_display_wait() {
+    let mut chip8 = Chip8::new(Quirks::vip());
+    chip8.load_rom(&DRAW_ROM).unwrap();
+    for _ in 0..3 {
+      chip8.run_frame().unwrap();
+    }
+    // Only one sprite is drawn per frame
+    assert_eq!(chip8.registers.get_v(1).unwrap(), 2);
+
+    let mut chip8 = Chip8::new(Quirks::schip_modern());
+    chip8.load_rom(&DRAW_ROM).unwrap();
+    assert_eq!(chip8.run_frame().unwrap(), 12);
+    assert_eq!(chip8.registers.get_v(1).unwrap(), 4);
+  }
+
+  #[test]
+  fn test_seed() {
+    // LD I, 0x300; RND V0, 0xFF; LD [I], V0; RND V1, 0x3F; RND V2, 0x1F; DRW V1, V2, 1; JP 0x202
+    let rom = [
+      0xA3, 0x00, 0xC0, 0xFF, 0xF0, 0x55, 0xC1, 0x3F, 0xC2, 0x1F, 0xD1, 0x21, 0x12, 0x02,
+    ];
+    let run = |seed| {
+      let mut chip8 = Chip8::with_seed(seed);
+      chip8.load_rom(&rom).unwrap();
+      for _ in 0..30 {
+        chip8.run_frame().unwrap();
+      }
+      (chip8.registers.v, chip8.frame().to_vec())
+    };
+    assert_eq!(run(1), run(1));
+    assert_ne!(run(1), run(2));
+  }
+
+  #[test]
+  fn test_step() {
+    let mut chip8 = Chip8::new(Quirks::vip());
+    chip8.set_cycles_per_frame(4);
+    chip8.load_rom(&DRAW_ROM).unwrap();
+    // The first DRW waits for the next frame, so the rest of it is idle
+    assert!(chip8.step().unwrap());
+    assert!(!chip8.step().unwrap());
+    assert_eq!(chip8.frame_count(), 1);
+    assert_eq!(chip8.run_frame().unwrap(), 0);
+    assert_eq!(chip8.frame_count(), 1);
+    for _ in 0..4 {
+      chip8.step().unwrap();
+    }
+    assert_eq!(chip8.frame_count(), 2);
+    assert_eq!(chip8.registers.get_v(1).unwrap(), 1);
+  }
+
+  #[test]
+  fn test_custom_rng() {
+    struct Constant(u8);
+
+    impl RandomSource for Constant {
+      fn next_u8(&mut self) -> u8 {
+        self.0
+      }
+    }
+
+    // RND V0, 0xFF
+    let mut chip8 = Chip8::with_rng(Variant::Chip8, Quirks::vip(), Constant(0x5A));
+    chip8.load_rom(&[0xC0, 0xFF]).unwrap();
+    chip8.step().unwrap();
+    assert_eq!(chip8.v()[0], 0x5A);
+  }
+
+  #[test]
+  fn test_schip() {
+    // HIGH; LD V0, 0x7F; LD V1, 0x3F; LD I, 0x280; DRW V0, V1, 1; EXIT
+    let rom = [0x00, 0xFF, 0x60, 0x7F, 0x61, 0x3F, 0xA2, 0x80, 0xD0, 0x11, 0x00, 0xFD];
+    let mut chip8 = Chip8::with_variant(Variant::SuperChip, Variant::SuperChip.quirks());
+    chip8.load_rom(&rom).unwrap();
+    chip8.memory.write_byte(0x280, 0x80).unwrap();
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.resolution(), (128, 64));
+    assert_eq!(chip8.frame()[128 * 64 - 1], 1);
+    assert!(chip8.exited());
+    assert_eq!(chip8.registers.pc, 0x20A);
+    // The base instruction set doesn't know about SUPER-CHIP opcodes
+    let mut chip8 = Chip8::default();
+    chip8.load_rom(&rom).unwrap();
+    assert!(chip8.run_frame().is_err());
+  }
+
+  #[test]
+  fn test_capacity() {
+    // HIGH; EXIT
+    let rom = [0x00, 0xFF, 0x00, 0xFD];
+    let mut chip8: Chip8<Rng, MEMORY_SIZE, LORES_PIXELS> =
+      Chip8::with_capacity(Variant::SuperChip, Variant::SuperChip.quirks(), Rng::new(0));
+    chip8.load_rom(&rom).unwrap();
+    assert!(matches!(chip8.run_frame(), Err(InterpreterError::HiresUnsupported)));
+    assert_eq!(chip8.resolution(), (64, 32));
+    assert!(core::mem::size_of_val(&chip8) < MEMORY_SIZE + LORES_PIXELS + 1024);
+  }
+
+  #[test]
+  fn test_xo_chip() {
+    // LD I, 0x2000; LD V0, 0x05; LD [I], V0; LD V1, 0x01; SE V1, 0x01; LD I, 0x3000; EXIT
+    let rom = [
+      0xF0, 0x00, 0x20, 0x00, 0x60, 0x05, 0xF0, 0x55, 0x61, 0x01, 0x31, 0x01, 0xF0, 0x00, 0x30,
+      0x00, 0x00, 0xFD,
+    ];
+    let mut chip8 = Chip8::with_variant(Variant::XoChip, Variant::XoChip.quirks());
+    chip8.load_rom(&rom).unwrap();
+    chip8.run_frame().unwrap();
+    assert!(chip8.exited());
+    assert_eq!(chip8.memory.read_byte(0x2000).unwrap(), 5);
+    assert_eq!(chip8.registers.i, 0x2001);
+  }
+
+  #[test]
+  fn test_set_key() {
+    let mut chip8 = Chip8::default();
+    // LD V0, 0x0C; SKP V0; JP 0x202; LD V1, 0x01
+    chip8.load_rom(&[0x60, 0x0C, 0xE0, 0x9E, 0x12, 0x02, 0x61, 0x01]).unwrap();
+    chip8.set_key(Chip8Key::Four, true);
+    assert!(chip8.registers.keydown(0xC).unwrap());
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.registers.get_v(1).unwrap(), 1);
+    chip8.set_key(Chip8Key::Four, false);
+    assert!(!chip8.registers.keydown(0xC).unwrap());
+  }
+}
@@ -0,0 +1,311 @@
+use super::error::*;
+use core::{
+  cell::Cell,
+  ops::{Deref, DerefMut},
+};
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const XO_MEMORY_SIZE: usize = 65536;
+pub const ROM_OFFSET: usize = 512;
+pub const FONT_OFFSET: usize = 80;
+const CHIP8_FONT: [u8; 80] = [
+  0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+  0x20, 0x60, 0x20, 0x20, 0x70, // 1
+  0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+  0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+  0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+  0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+  0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+  0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+  0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+  0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+  0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+  0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+  0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+  0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+  0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+  0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+pub const BIG_FONT_OFFSET: usize = FONT_OFFSET + CHIP8_FONT.len();
+const SCHIP_FONT: [u8; 160] = [
+  0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+  0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+  0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+  0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+  0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+  0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+  0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+  0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+  0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+  0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+  0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+  0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+  0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+  0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+  0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+  0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+pub const MAX_WATCHPOINTS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+  Read,
+  Write,
+  ReadWrite,
+}
+
+impl Access {
+  fn includes(self, access: Access) -> bool {
+    self == Access::ReadWrite || self == access
+  }
+}
+
+// Catches instructions touching `len` bytes from `address`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+  pub address: usize,
+  pub len: usize,
+  pub access: Access,
+}
+
+// The first watched access since the last `take_watch_hit`, either a read or
+// a write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+  pub address: usize,
+  pub access: Access,
+}
+
+// Only the first `size` bytes are addressable. The storage is generic so that
+// machines can be built with just enough memory for the variants they run;
+// everything else works on the unsized `Memory`, which any capacity coerces to.
+pub struct Memory<S: ?Sized = [u8]> {
+  size: usize,
+  // Reads come through &self, so hits are recorded in a Cell
+  watchpoints: [Option<Watchpoint>; MAX_WATCHPOINTS],
+  watch_hit: Cell<Option<WatchHit>>,
+  mem: S,
+}
+
+impl<const CAPACITY: usize> Default for Memory<[u8; CAPACITY]> {
+  fn default() -> Self {
+    Self::new(MEMORY_SIZE)
+  }
+}
+
+impl<const CAPACITY: usize> Memory<[u8; CAPACITY]> {
+  // `size` is clamped to the capacity, which has to fit at least a CHIP-8's
+  // 4 KB
+  pub fn new(size: usize) -> Self {
+    const { assert!(CAPACITY >= MEMORY_SIZE, "memory must hold at least 4 KB") };
+    let mut mem = [0; CAPACITY];
+    mem[FONT_OFFSET..FONT_OFFSET + CHIP8_FONT.len()].copy_from_slice(&CHIP8_FONT);
+    mem[BIG_FONT_OFFSET..BIG_FONT_OFFSET + SCHIP_FONT.len()].copy_from_slice(&SCHIP_FONT);
+    Self {
+      size: size.min(CAPACITY),
+      watchpoints: [None; MAX_WATCHPOINTS],
+      watch_hit: Cell::new(None),
+      mem,
+    }
+  }
+}
+
+impl<const CAPACITY: usize> Deref for Memory<[u8; CAPACITY]> {
+  type Target = Memory;
+
+  fn deref(&self) -> &Memory {
+    self
+  }
+}
+
+impl<const CAPACITY: usize> DerefMut for Memory<[u8; CAPACITY]> {
+  fn deref_mut(&mut self) -> &mut Memory {
+    self
+  }
+}
+
+impl Memory {
+  pub fn size(&self) -> usize {
+    self.size
+  }
+
+  pub fn read(&self, addr: usize, len: usize) -> Result<&[u8], InterpreterError> {
+    let bytes = self.peek(addr, len)?;
+    self.watch(addr, len, Access::Read);
+    Ok(bytes)
+  }
+
+  pub fn read_byte(&self, addr: usize) -> Result<u8, InterpreterError> {
+    Ok(self.read(addr, 1)?[0])
+  }
+
+  // Reads without tripping watchpoints, for fetching instructions and for
+  // looking at memory from outside the machine
+  pub fn peek(&self, addr: usize, len: usize) -> Result<&[u8], InterpreterError> {
+    let end = addr + len;
+    if end <= self.size {
+      Ok(&self.mem[addr..end])
+    } else {
+      Err(InterpreterError::InvalidAddressError(addr))
+    }
+  }
+
+  pub fn write(&mut self, addr: usize, data: &[u8]) -> Result<(), InterpreterError> {
+    self.store(addr, data)?;
+    self.watch(addr, data.len(), Access::Write);
+    Ok(())
+  }
+
+  pub fn write_byte(&mut self, addr: usize, byte: u8) -> Result<(), InterpreterError> {
+    self.write(addr, &[byte])
+  }
+
+  // Writes without tripping watchpoints
+  fn store(&mut self, addr: usize, data: &[u8]) -> Result<(), InterpreterError> {
+    if addr + data.len() > self.size {
+      Err(InterpreterError::InvalidAddressError(addr))
+    } else {
+      self.mem[addr..addr + data.len()].copy_from_slice(data);
+      Ok(())
+    }
+  }
+
+  pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), InterpreterError> {
+    self.store(ROM_OFFSET, rom)?;
+    Ok(())
+  }
+
+  pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> Result<(), InterpreterError> {
+    match self.watchpoints.iter_mut().find(|slot| slot.is_none()) {
+      Some(slot) => {
+        *slot = Some(watchpoint);
+        Ok(())
+      }
+      None => Err(InterpreterError::TooManyWatchpoints),
+    }
+  }
+
+  // Removes the watchpoints starting at the address, returning whether there
+  // were any
+  pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+    let mut removed = false;
+    for slot in &mut self.watchpoints {
+      if slot.is_some_and(|watchpoint| watchpoint.address == address) {
+        *slot = None;
+        removed = true;
+      }
+    }
+    removed
+  }
+
+  pub fn clear_watchpoints(&mut self) {
+    self.watchpoints = [None; MAX_WATCHPOINTS];
+  }
+
+  pub fn watchpoints(&self) -> impl Iterator<Item = &Watchpoint> {
+    self.watchpoints.iter().flatten()
+  }
+
+  pub fn take_watch_hit(&self) -> Option<WatchHit> {
+    self.watch_hit.take()
+  }
+
+  // Carries the watchpoints over to memory that's replacing this one
+  #[cfg(feature = "alloc")]
+  pub(crate) fn copy_watchpoints(&self, to: &mut Memory) {
+    to.watchpoints = self.watchpoints;
+  }
+
+  fn watch(&self, addr: usize, len: usize, access: Access) {
+    if self.watch_hit.get().is_some() {
+      return;
+    }
+    let hit = self.watchpoints().find_map(|watchpoint| {
+      let start = addr.max(watchpoint.address);
+      let overlaps = start < (addr + len).min(watchpoint.address + watchpoint.len);
+      (overlaps && watchpoint.access.includes(access)).then_some(WatchHit { address: start, access })
+    });
+    self.watch_hit.set(hit);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_read_write() {
+    let mut mem: Memory<[u8; MEMORY_SIZE]> = Memory::default();
+    assert!(mem.write(0xF00, &[1, 2, 3]).is_ok());
+    assert!(mem.write(0xFFF + 2, &[1, 2, 3]).is_err());
+    assert_eq!(mem.read(0xF00, 3).unwrap(), &[1, 2, 3]);
+    assert!(mem.write_byte(0xF00, 10).is_ok());
+    assert!(mem.write_byte(0xFFF + 2, 10).is_err());
+    assert_eq!(mem.read_byte(0xF00).unwrap(), 10);
+  }
+
+  #[test]
+  fn test_bounds() {
+    let mut mem: Memory<[u8; MEMORY_SIZE]> = Memory::default();
+    assert!(mem.write(0xFFD, &[1, 2, 3]).is_ok());
+    assert_eq!(mem.read(0xFFD, 3).unwrap(), &[1, 2, 3]);
+    assert!(mem.read(0xFFE, 3).is_err());
+    assert!(mem.read_byte(0x1000).is_err());
+    assert!(mem.load_rom(&[0; MEMORY_SIZE - ROM_OFFSET]).is_ok());
+    assert!(mem.load_rom(&[0; MEMORY_SIZE - ROM_OFFSET + 1]).is_err());
+  }
+
+  #[test]
+  fn test_watchpoints() {
+    let mut mem: Memory<[u8; MEMORY_SIZE]> = Memory::default();
+    mem.add_watchpoint(Watchpoint { address: 0x300, len: 4, access: Access::Write }).unwrap();
+    mem.add_watchpoint(Watchpoint { address: 0x400, len: 1, access: Access::ReadWrite }).unwrap();
+    mem.load_rom(&[1, 2, 3]).unwrap();
+    mem.read(0x300, 4).unwrap();
+    mem.write(0x2FE, &[0; 2]).unwrap();
+    assert_eq!(mem.take_watch_hit(), None);
+
+    mem.write(0x2FE, &[0; 4]).unwrap();
+    assert_eq!(mem.take_watch_hit(), Some(WatchHit { address: 0x300, access: Access::Write }));
+    assert_eq!(mem.take_watch_hit(), None);
+    // Only the first hit is kept until it's taken
+    mem.read_byte(0x400).unwrap();
+    mem.write_byte(0x303, 0).unwrap();
+    assert_eq!(mem.take_watch_hit(), Some(WatchHit { address: 0x400, access: Access::Read }));
+    mem.peek(0x400, 1).unwrap();
+    assert_eq!(mem.take_watch_hit(), None);
+
+    assert!(mem.remove_watchpoint(0x400));
+    assert!(!mem.remove_watchpoint(0x400));
+    assert_eq!(mem.watchpoints().count(), 1);
+    for _ in 1..MAX_WATCHPOINTS {
+      mem.add_watchpoint(Watchpoint { address: 0, len: 1, access: Access::Read }).unwrap();
+    }
+    assert!(mem.add_watchpoint(Watchpoint { address: 0, len: 1, access: Access::Read }).is_err());
+    mem.clear_watchpoints();
+    assert_eq!(mem.watchpoints().count(), 0);
+  }
+
+  #[test]
+  fn test_xo_memory() {
+    let mut mem: Memory<[u8; XO_MEMORY_SIZE]> = Memory::new(XO_MEMORY_SIZE);
+    assert_eq!(mem.size(), 65536);
+    assert!(mem.write_byte(0xFFFF, 10).is_ok());
+    assert_eq!(mem.read_byte(0xFFFF).unwrap(), 10);
+    assert!(mem.write_byte(0x10000, 10).is_err());
+    assert_eq!(mem.read_byte(FONT_OFFSET).unwrap(), 0xF0);
+  }
+
+  #[test]
+  fn test_capacity() {
+    // Extra capacity isn't addressable until the size asks for it
+    let mut mem: Memory<[u8; XO_MEMORY_SIZE]> = Memory::default();
+    assert!(mem.write_byte(0x1000, 10).is_err());
+    // And the size can't go past the capacity
+    let mut mem: Memory<[u8; MEMORY_SIZE]> = Memory::new(XO_MEMORY_SIZE);
+    assert_eq!(mem.size(), MEMORY_SIZE);
+    assert!(mem.write_byte(0x1000, 10).is_err());
+    assert_eq!(core::mem::size_of_val(&mem.mem), MEMORY_SIZE);
+  }
+}
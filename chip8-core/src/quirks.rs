@@ -0,0 +1,86 @@
+// Behaviours that differ between the CHIP-8 interpreters over the years. See
+// https://github.com/Timendus/chip8-test-suite#quirks-test for the details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+  // OR, AND and XOR reset VF to zero
+  pub vf_reset: bool,
+  // LD Vx, K waits for the key to be released before completing
+  pub key_release: bool,
+  // LD [I], Vx and LD Vx, [I] leave I pointing past the last register
+  pub memory: bool,
+  // Along with `memory`, I is only moved on by X, one short of the last
+  // register, as on the CHIP-48
+  pub memory_increment_by_x: bool,
+  // Sprites are clipped at the edges of the screen instead of wrapping
+  pub clipping: bool,
+  // SHR and SHL shift Vx in place, ignoring Vy
+  pub shifting: bool,
+  // DRW waits for the vertical blank, so at most one sprite is drawn per frame
+  pub display_wait: bool,
+  // JP V0, addr jumps to addr + Vx, where x is the highest nibble of addr
+  pub jumping: bool,
+}
+
+impl Default for Quirks {
+  fn default() -> Self {
+    Self::vip()
+  }
+}
+
+impl Quirks {
+  // The original COSMAC VIP interpreter
+  pub const fn vip() -> Self {
+    Self {
+      vf_reset: true,
+      key_release: true,
+      memory: true,
+      memory_increment_by_x: false,
+      clipping: true,
+      shifting: false,
+      display_wait: true,
+      jumping: false,
+    }
+  }
+
+  // SUPER-CHIP 1.1 as most modern interpreters implement it
+  pub const fn schip_modern() -> Self {
+    Self {
+      vf_reset: false,
+      key_release: true,
+      memory: false,
+      memory_increment_by_x: false,
+      clipping: true,
+      shifting: true,
+      display_wait: false,
+      jumping: true,
+    }
+  }
+
+  // SUPER-CHIP 1.1 as it behaved on the HP 48 calculators
+  pub const fn schip_legacy() -> Self {
+    Self {
+      vf_reset: false,
+      key_release: true,
+      memory: false,
+      memory_increment_by_x: false,
+      clipping: true,
+      shifting: true,
+      display_wait: true,
+      jumping: true,
+    }
+  }
+
+  // XO-CHIP, as implemented by Octo
+  pub const fn xo_chip() -> Self {
+    Self {
+      vf_reset: false,
+      key_release: true,
+      memory: true,
+      memory_increment_by_x: false,
+      clipping: false,
+      shifting: false,
+      display_wait: false,
+      jumping: false,
+    }
+  }
+}
@@ -0,0 +1,211 @@
+use super::error::*;
+
+pub const MAX_STACK: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
+// A 500 Hz square wave at the default pitch, used until a ROM loads its own
+// pattern
+pub const DEFAULT_AUDIO_PATTERN: [u8; 16] = [0xF0; 16];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Key {
+  X,
+  One,
+  Two,
+  Three,
+  Q,
+  W,
+  E,
+  A,
+  S,
+  D,
+  Z,
+  C,
+  Four,
+  R,
+  F,
+  V,
+}
+
+impl Chip8Key {
+  // In keypad order, so a key's position is its index
+  pub const ALL: [Chip8Key; 16] = [
+    Chip8Key::X,
+    Chip8Key::One,
+    Chip8Key::Two,
+    Chip8Key::Three,
+    Chip8Key::Q,
+    Chip8Key::W,
+    Chip8Key::E,
+    Chip8Key::A,
+    Chip8Key::S,
+    Chip8Key::D,
+    Chip8Key::Z,
+    Chip8Key::C,
+    Chip8Key::Four,
+    Chip8Key::R,
+    Chip8Key::F,
+    Chip8Key::V,
+  ];
+
+  pub fn index(self) -> usize {
+    self as usize
+  }
+
+  pub fn from_index(index: usize) -> Option<Self> {
+    Self::ALL.get(index).copied()
+  }
+}
+
+// Where LD Vx, K is in its wait for a full press and release
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyWait {
+  Press,
+  Release(usize),
+}
+
+pub struct Registers {
+  pub pc: u16,
+  pub i: u16,
+  // Only the first `sp` entries are in use
+  pub stack: [u16; MAX_STACK],
+  pub sp: usize,
+  pub v: [u8; 16],
+  pub keys: [bool; 16],
+  pub released_keys: [bool; 16],
+  pub key_wait: Option<KeyWait>,
+  pub delay_timer: u8,
+  pub sound_timer: u8,
+  pub display_wait: bool,
+  pub exited: bool,
+  pub rpl: [u8; 16],
+  pub audio_pattern: [u8; 16],
+  pub pitch: u8,
+}
+
+impl Default for Registers {
+  fn default() -> Self {
+      Self {
+        pc: 512,
+        i: 0,
+        stack: [0; MAX_STACK],
+        sp: 0,
+        v: [0; 16],
+        keys: [false; 16],
+        released_keys: [false; 16],
+        key_wait: None,
+        delay_timer: 0,
+        sound_timer: 0,
+        display_wait: false,
+        exited: false,
+        rpl: [0; 16],
+        audio_pattern: DEFAULT_AUDIO_PATTERN,
+        pitch: DEFAULT_PITCH,
+      }
+  }
+}
+
+impl Registers {
+  pub fn push(&mut self, addr: u16) -> Result<(), InterpreterError> {
+    if self.sp < MAX_STACK {
+      self.stack[self.sp] = addr;
+      self.sp += 1;
+      Ok(())
+    } else {
+      Err(InterpreterError::StackOverflow)
+    }
+  }
+
+  pub fn pop(&mut self) -> Result<u16, InterpreterError> {
+    if self.sp > 0 {
+      self.sp -= 1;
+      Ok(self.stack[self.sp])
+    } else {
+      Err(InterpreterError::StackUnderflow)
+    }
+  }
+
+  // The return addresses currently on the stack, oldest first
+  pub fn stack(&self) -> &[u16] {
+    &self.stack[..self.sp]
+  }
+
+  pub fn get_v(&self, index: usize) -> Result<u8, InterpreterError> {
+    if index > 15 {
+      Err(InterpreterError::InvalidRegister(index))
+    } else {
+      Ok(self.v[index])
+    }
+  }
+
+  pub fn set_v(&mut self, index: usize, value: u8) -> Result<(), InterpreterError> {
+    if index > 15 {
+      Err(InterpreterError::InvalidRegister(index))
+    } else {
+      self.v[index] = value;
+      Ok(())
+    }
+  }
+
+  pub fn get_vf(&self) -> u8 {
+    self.v[15]
+  }
+
+  pub fn set_vf(&mut self, value: u8) {
+    self.v[15] = value;
+  }
+
+  pub fn get_dt(&self) -> u8 {
+    self.delay_timer
+  }
+
+  pub fn set_dt(&mut self, value: u8) {
+    self.delay_timer = value;
+  }
+
+  pub fn get_st(&self) -> u8 {
+    self.sound_timer
+  }
+
+  pub fn set_st(&mut self, value: u8) {
+    self.sound_timer = value;
+  }
+
+  pub fn tick_timers(&mut self) {
+    self.delay_timer = self.delay_timer.saturating_sub(1);
+    self.sound_timer = self.sound_timer.saturating_sub(1);
+  }
+
+  pub fn keydown(&self, index: usize) -> Result<bool, InterpreterError> {
+    if index > 15 {
+      Err(InterpreterError::InvalidKey(index))
+    } else {
+      Ok(self.keys[index])
+    }
+  }
+
+  pub fn set_key(&mut self, key: Chip8Key, pressed: bool) {
+    let index = key.index();
+    if self.keys[index] && !pressed {
+      self.released_keys[index] = true;
+    }
+    self.keys[index] = pressed;
+  }
+
+  pub fn take_released(&mut self, index: usize) -> bool {
+    let released = self.released_keys[index];
+    self.released_keys[index] = false;
+    released
+  }
+
+  pub fn clear_released(&mut self) {
+    self.released_keys = [false; 16];
+  }
+
+  pub fn first_released(&self) -> Option<usize> {
+    (0..16).find(|&i| self.released_keys[i])
+  }
+
+  pub fn first_keydown(&self) -> Option<usize> {
+    (0..16).find(|&i| self.keys[i])
+  }
+}
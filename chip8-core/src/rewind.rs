@@ -0,0 +1,203 @@
+use super::{error::*, rng::RandomSource, Chip8};
+use alloc::{collections::VecDeque, vec::Vec};
+
+// A ring buffer of recent save states, taken every `interval` frames.
+//
+// Only the newest snapshot is kept whole. Each older one is stored as the XOR
+// of itself and the snapshot after it, run length encoded. Consecutive frames
+// barely differ, so the XOR is almost all zeroes and compresses to a few bytes.
+pub struct Rewinder {
+  capacity: usize,
+  interval: u64,
+  last_frame: Option<u64>,
+  head: Option<Vec<u8>>,
+  deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewinder {
+  pub fn new(capacity: usize, interval: u64) -> Self {
+    Self {
+      capacity: capacity.max(1),
+      interval: interval.max(1),
+      last_frame: None,
+      head: None,
+      deltas: VecDeque::new(),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.head.as_ref().map_or(0, |_| self.deltas.len() + 1)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.head.is_none()
+  }
+
+  pub fn clear(&mut self) {
+    self.last_frame = None;
+    self.head = None;
+    self.deltas.clear();
+  }
+
+  // Call once per frame. Takes a snapshot if at least `interval` frames have
+  // run since the last one.
+  pub fn record<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(&mut self, chip8: &Chip8<R, MEMORY, PIXELS>) {
+    let frame = chip8.frame_count();
+    if self.last_frame.is_some_and(|last| frame < last + self.interval) {
+      return;
+    }
+    self.last_frame = Some(frame);
+    self.push(chip8.save_state());
+  }
+
+  // Restores the newest snapshot and drops it, so calling this repeatedly
+  // steps further back. Returns false once the history is exhausted.
+  pub fn rewind<R: RandomSource, const MEMORY: usize, const PIXELS: usize>(&mut self, chip8: &mut Chip8<R, MEMORY, PIXELS>) -> InterpretterResult<bool> {
+    let Some(state) = self.head.take() else {
+      return Ok(false);
+    };
+    chip8.load_state(&state)?;
+    self.head = self.deltas.pop_back().map(|delta| apply_delta(&state, &delta));
+    self.last_frame = Some(chip8.frame_count());
+    Ok(true)
+  }
+
+  fn push(&mut self, state: Vec<u8>) {
+    if let Some(previous) = self.head.take() {
+      self.deltas.push_back(encode_delta(&state, &previous));
+      if self.deltas.len() >= self.capacity {
+        self.deltas.pop_front();
+      }
+    }
+    self.head = Some(state);
+  }
+}
+
+// Deltas are a list of (zero run, literal count, literals) records with the
+// counts as LEB128, preceded by the length of the target state
+fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+  let mut delta = Vec::new();
+  write_varint(&mut delta, target.len());
+  let xor: Vec<u8> = (0..target.len())
+    .map(|i| target[i] ^ base.get(i).copied().unwrap_or(0))
+    .collect();
+  let mut i = 0;
+  while i < xor.len() {
+    let zeroes = xor[i..].iter().take_while(|&&byte| byte == 0).count();
+    i += zeroes;
+    let literals = xor[i..].iter().take_while(|&&byte| byte != 0).count();
+    write_varint(&mut delta, zeroes);
+    write_varint(&mut delta, literals);
+    delta.extend_from_slice(&xor[i..i + literals]);
+    i += literals;
+  }
+  delta
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+  let mut delta = delta.iter().copied();
+  let len = read_varint(&mut delta);
+  let mut target: Vec<u8> = (0..len).map(|i| base.get(i).copied().unwrap_or(0)).collect();
+  let mut i = 0;
+  while i < len {
+    i += read_varint(&mut delta);
+    for _ in 0..read_varint(&mut delta) {
+      target[i] ^= delta.next().unwrap_or(0);
+      i += 1;
+    }
+  }
+  target
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+  while value >= 0x80 {
+    out.push(value as u8 | 0x80);
+    value >>= 7;
+  }
+  out.push(value as u8);
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> usize {
+  let mut value = 0;
+  let mut shift = 0;
+  for byte in bytes {
+    value |= ((byte & 0x7F) as usize) << shift;
+    if byte & 0x80 == 0 {
+      break;
+    }
+    shift += 7;
+  }
+  value
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // ADD V0, 0x01; JP 0x200
+  const COUNTER_ROM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+  fn counter() -> Chip8 {
+    let mut chip8 = Chip8::with_seed(0);
+    chip8.set_cycles_per_frame(2);
+    chip8.load_rom(&COUNTER_ROM).unwrap();
+    chip8
+  }
+
+  #[test]
+  fn test_delta() {
+    let base = vec![1, 2, 3, 4, 5, 6, 7, 8];
+    for target in [vec![1, 2, 9, 4, 5, 6, 7, 0], vec![1, 2], vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10]] {
+      assert_eq!(apply_delta(&base, &encode_delta(&base, &target)), target);
+    }
+    let mut large = vec![0; 5000];
+    let base = large.clone();
+    large[4000] = 1;
+    assert!(encode_delta(&base, &large).len() < 10);
+    assert_eq!(apply_delta(&base, &encode_delta(&base, &large)), large);
+  }
+
+  #[test]
+  fn test_record_interval() {
+    let mut chip8 = counter();
+    let mut rewinder = Rewinder::new(100, 4);
+    for _ in 0..10 {
+      rewinder.record(&chip8);
+      chip8.run_frame().unwrap();
+    }
+    // Frames 0, 4 and 8
+    assert_eq!(rewinder.len(), 3);
+  }
+
+  #[test]
+  fn test_rewind() {
+    let mut chip8 = counter();
+    let mut rewinder = Rewinder::new(100, 1);
+    let mut history = Vec::new();
+    for _ in 0..20 {
+      rewinder.record(&chip8);
+      history.push(chip8.registers.v[0]);
+      chip8.run_frame().unwrap();
+    }
+    while let Some(v0) = history.pop() {
+      assert!(rewinder.rewind(&mut chip8).unwrap());
+      assert_eq!(chip8.registers.v[0], v0);
+    }
+    assert!(rewinder.is_empty());
+    assert!(!rewinder.rewind(&mut chip8).unwrap());
+  }
+
+  #[test]
+  fn test_capacity() {
+    let mut chip8 = counter();
+    let mut rewinder = Rewinder::new(5, 1);
+    for _ in 0..20 {
+      rewinder.record(&chip8);
+      chip8.run_frame().unwrap();
+    }
+    assert_eq!(rewinder.len(), 5);
+    while rewinder.rewind(&mut chip8).unwrap() {}
+    // Frame 15 is the oldest one left, two instructions per frame
+    assert_eq!(chip8.registers.v[0], 15);
+  }
+}
@@ -0,0 +1,76 @@
+// Where RND gets its random bytes from. Implement this to plug in a hardware
+// generator, or anything else that isn't the built-in SplitMix64.
+pub trait RandomSource {
+  fn next_u8(&mut self) -> u8;
+
+  // Sources that can be snapshotted expose their state here, so that save
+  // states replay the same sequence. Stateless sources can ignore it.
+  fn state(&self) -> u64 {
+    0
+  }
+
+  fn set_state(&mut self, _state: u64) {}
+}
+
+// SplitMix64. Small, fast and its whole state is a single u64, which makes
+// it trivial to seed, snapshot and restore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  pub fn new(seed: u64) -> Self {
+    Self {
+      state: seed,
+    }
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+}
+
+impl RandomSource for Rng {
+  fn next_u8(&mut self) -> u8 {
+    (self.next_u64() >> 56) as u8
+  }
+
+  fn state(&self) -> u64 {
+    self.state
+  }
+
+  fn set_state(&mut self, state: u64) {
+    self.state = state;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_seed() {
+    let mut a = Rng::new(1234);
+    let mut b = Rng::new(1234);
+    let mut c = Rng::new(4321);
+    let a_values: [u8; 32] = core::array::from_fn(|_| a.next_u8());
+    let b_values: [u8; 32] = core::array::from_fn(|_| b.next_u8());
+    let c_values: [u8; 32] = core::array::from_fn(|_| c.next_u8());
+    assert_eq!(a_values, b_values);
+    assert_ne!(a_values, c_values);
+  }
+
+  #[test]
+  fn test_state() {
+    let mut rng = Rng::new(0);
+    rng.next_u64();
+    let mut restored = Rng::new(99);
+    restored.set_state(rng.state());
+    assert_eq!(rng.next_u64(), restored.next_u64());
+  }
+}
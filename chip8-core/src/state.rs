@@ -0,0 +1,469 @@
+use super::{
+  error::*,
+  frame_buffer::*,
+  instructions::InstructionSet,
+  memory::*,
+  quirks::Quirks,
+  registers::{KeyWait, Registers, MAX_STACK},
+  rng::RandomSource,
+  variant::Variant,
+  Chip8,
+};
+use alloc::vec::Vec;
+
+// Save state layout, all integers little endian:
+//
+//   magic     4 bytes  "C8SS"
+//   version   u16
+//   length    u32      length of the payload
+//   payload   length bytes
+//   checksum  u32      CRC-32 of everything before it
+//
+// Version 1 payload:
+//
+//   variant u8, quirks u8 (bit flags), cycles per frame u32, rng state u64
+//   memory size u32, memory
+//   pc u16, i u16, stack depth u8, stack u16 * depth, v 16 bytes,
+//   keys u16, released keys u16, key wait u8 (0xFF for none, 0xFE while
+//   waiting for a press, otherwise the key waiting to be released), delay timer u8,
+//   sound timer u8, display wait u8, exited u8, rpl 16 bytes,
+//   audio pattern 16 bytes, pitch u8
+//   hires u8, planes u8, one byte per pixel at the stored resolution
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u16 = 1;
+const HEADER_SIZE: usize = 10;
+const CHECKSUM_SIZE: usize = 4;
+const NO_KEY_WAIT: u8 = 0xFF;
+const KEY_WAIT_PRESS: u8 = 0xFE;
+
+impl<R: RandomSource, const MEMORY: usize, const PIXELS: usize> Chip8<R, MEMORY, PIXELS> {
+  // Snapshots the whole machine. The wall clock isn't included, so a restored
+  // machine starts on a frame boundary.
+  pub fn save_state(&self) -> Vec<u8> {
+    let mut payload = Writer::default();
+    payload.u8(self.variant as u8);
+    payload.u8(quirks_to_bits(&self.quirks));
+    payload.u32(self.cycles_per_frame as u32);
+    payload.u64(self.rng.state());
+
+    let size = self.memory.size();
+    payload.u32(size as u32);
+    payload.bytes(self.memory.peek(0, size).expect("memory is always readable up to its size"));
+
+    let registers = &self.registers;
+    payload.u16(registers.pc);
+    payload.u16(registers.i);
+    payload.u8(registers.sp as u8);
+    registers.stack().iter().for_each(|&addr| payload.u16(addr));
+    payload.bytes(&registers.v);
+    payload.u16(keys_to_bits(&registers.keys));
+    payload.u16(keys_to_bits(&registers.released_keys));
+    payload.u8(match registers.key_wait {
+      None => NO_KEY_WAIT,
+      Some(KeyWait::Press) => KEY_WAIT_PRESS,
+      Some(KeyWait::Release(key)) => key as u8,
+    });
+    payload.u8(registers.delay_timer);
+    payload.u8(registers.sound_timer);
+    payload.u8(registers.display_wait as u8);
+    payload.u8(registers.exited as u8);
+    payload.bytes(&registers.rpl);
+    payload.bytes(&registers.audio_pattern);
+    payload.u8(registers.pitch);
+
+    payload.u8(self.frame_buffer.hires() as u8);
+    payload.u8(self.frame_buffer.planes());
+    payload.bytes(self.frame_buffer.frame());
+
+    let mut state = Writer::default();
+    state.bytes(MAGIC);
+    state.u16(VERSION);
+    state.u32(payload.0.len() as u32);
+    state.bytes(&payload.0);
+    let checksum = crc32(&state.0);
+    state.u32(checksum);
+    state.0
+  }
+
+  // Restores a snapshot taken by `save_state`. The state is fully validated
+  // before anything is replaced, so on error the machine is left untouched.
+  pub fn load_state(&mut self, state: &[u8]) -> InterpretterResult {
+    if state.len() < HEADER_SIZE + CHECKSUM_SIZE || &state[..4] != MAGIC {
+      return Err(InterpreterError::InvalidStateHeader);
+    }
+    let mut header = Reader::new(&state[4..HEADER_SIZE]);
+    let version = header.u16()?;
+    if version != VERSION {
+      return Err(InterpreterError::UnsupportedStateVersion(version));
+    }
+    let length = header.u32()? as usize;
+    if state.len() != HEADER_SIZE + length + CHECKSUM_SIZE {
+      return Err(InterpreterError::CorruptState("length doesn't match the header"));
+    }
+    let (body, checksum) = state.split_at(HEADER_SIZE + length);
+    if crc32(body) != Reader::new(checksum).u32()? {
+      return Err(InterpreterError::StateChecksumMismatch);
+    }
+
+    let mut payload = Reader::new(&body[HEADER_SIZE..]);
+    let variant = match payload.u8()? {
+      0 => Variant::Chip8,
+      1 => Variant::SuperChip,
+      2 => Variant::XoChip,
+      _ => return Err(InterpreterError::CorruptState("unknown variant")),
+    };
+    let quirks = quirks_from_bits(payload.u8()?);
+    let cycles_per_frame = payload.u32()? as usize;
+    if cycles_per_frame == 0 {
+      return Err(InterpreterError::CorruptState("no cycles per frame"));
+    }
+    let rng_state = payload.u64()?;
+
+    let size = payload.u32()? as usize;
+    if size != variant.memory_size().min(MEMORY) {
+      return Err(InterpreterError::CorruptState("memory size doesn't match the variant"));
+    }
+    let mut memory = Memory::new(size);
+    memory.write(0, payload.bytes(size)?)?;
+
+    let mut registers = Registers {
+      pc: payload.u16()?,
+      i: payload.u16()?,
+      ..Registers::default()
+    };
+    let depth = payload.u8()? as usize;
+    if depth > MAX_STACK {
+      return Err(InterpreterError::CorruptState("stack is too deep"));
+    }
+    for _ in 0..depth {
+      registers.push(payload.u16()?)?;
+    }
+    registers.v.copy_from_slice(payload.bytes(16)?);
+    registers.keys = keys_from_bits(payload.u16()?);
+    registers.released_keys = keys_from_bits(payload.u16()?);
+    registers.key_wait = match payload.u8()? {
+      NO_KEY_WAIT => None,
+      KEY_WAIT_PRESS => Some(KeyWait::Press),
+      key if key < 16 => Some(KeyWait::Release(key as usize)),
+      _ => return Err(InterpreterError::CorruptState("invalid key wait")),
+    };
+    registers.delay_timer = payload.u8()?;
+    registers.sound_timer = payload.u8()?;
+    registers.display_wait = payload.u8()? != 0;
+    registers.exited = payload.u8()? != 0;
+    registers.rpl.copy_from_slice(payload.bytes(16)?);
+    registers.audio_pattern.copy_from_slice(payload.bytes(16)?);
+    registers.pitch = payload.u8()?;
+
+    let hires = payload.u8()? != 0;
+    let planes = payload.u8()?;
+    let (width, height) = if hires {
+      (HIRES_WIDTH, HIRES_HEIGHT)
+    } else {
+      (LORES_WIDTH, LORES_HEIGHT)
+    };
+    let mut frame_buffer = FrameBuffer::default();
+    frame_buffer.restore(hires, planes, payload.bytes(width as usize * height as usize)?)?;
+
+    if !payload.is_empty() {
+      return Err(InterpreterError::CorruptState("trailing data"));
+    }
+
+    if variant != self.variant {
+      self.instructions = InstructionSet::new(variant);
+    }
+    self.variant = variant;
+    self.quirks = quirks;
+    self.cycles_per_frame = cycles_per_frame;
+    self.rng.set_state(rng_state);
+    self.frame_cycle = 0;
+    // Watchpoints belong to whoever is debugging, not to the snapshot
+    self.memory.copy_watchpoints(&mut memory);
+    self.memory = memory;
+    self.registers = registers;
+    self.frame_buffer = frame_buffer;
+    Ok(())
+  }
+}
+
+fn quirks_to_bits(quirks: &Quirks) -> u8 {
+  [
+    quirks.vf_reset,
+    quirks.key_release,
+    quirks.memory,
+    quirks.clipping,
+    quirks.shifting,
+    quirks.display_wait,
+    quirks.jumping,
+    quirks.memory_increment_by_x,
+  ]
+  .iter()
+  .enumerate()
+  .fold(0, |bits, (bit, &set)| bits | ((set as u8) << bit))
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+  let bit = |n: u8| bits & (1 << n) != 0;
+  Quirks {
+    vf_reset: bit(0),
+    key_release: bit(1),
+    memory: bit(2),
+    clipping: bit(3),
+    shifting: bit(4),
+    display_wait: bit(5),
+    jumping: bit(6),
+    memory_increment_by_x: bit(7),
+  }
+}
+
+fn keys_to_bits(keys: &[bool; 16]) -> u16 {
+  keys
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (key, &down)| bits | ((down as u16) << key))
+}
+
+fn keys_from_bits(bits: u16) -> [bool; 16] {
+  let mut keys = [false; 16];
+  for (key, down) in keys.iter_mut().enumerate() {
+    *down = bits & (1 << key) != 0;
+  }
+  keys
+}
+
+// CRC-32 (IEEE), bit at a time. Save states are small enough that a lookup
+// table isn't worth it.
+fn crc32(data: &[u8]) -> u32 {
+  let mut crc = !0u32;
+  for &byte in data {
+    crc ^= byte as u32;
+    for _ in 0..8 {
+      let mask = (crc & 1).wrapping_neg();
+      crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+    }
+  }
+  !crc
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+  fn bytes(&mut self, bytes: &[u8]) {
+    self.0.extend_from_slice(bytes);
+  }
+
+  fn u8(&mut self, value: u8) {
+    self.0.push(value);
+  }
+
+  fn u16(&mut self, value: u16) {
+    self.bytes(&value.to_le_bytes());
+  }
+
+  fn u32(&mut self, value: u32) {
+    self.bytes(&value.to_le_bytes());
+  }
+
+  fn u64(&mut self, value: u64) {
+    self.bytes(&value.to_le_bytes());
+  }
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn new(data: &'a [u8]) -> Self {
+    Self {
+      data,
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  fn bytes(&mut self, len: usize) -> InterpretterResult<&'a [u8]> {
+    if len > self.data.len() {
+      return Err(InterpreterError::CorruptState("unexpected end of data"));
+    }
+    let (bytes, rest) = self.data.split_at(len);
+    self.data = rest;
+    Ok(bytes)
+  }
+
+  fn array<const N: usize>(&mut self) -> InterpretterResult<[u8; N]> {
+    let mut array = [0; N];
+    array.copy_from_slice(self.bytes(N)?);
+    Ok(array)
+  }
+
+  fn u8(&mut self) -> InterpretterResult<u8> {
+    Ok(self.array::<1>()?[0])
+  }
+
+  fn u16(&mut self) -> InterpretterResult<u16> {
+    Ok(u16::from_le_bytes(self.array()?))
+  }
+
+  fn u32(&mut self) -> InterpretterResult<u32> {
+    Ok(u32::from_le_bytes(self.array()?))
+  }
+
+  fn u64(&mut self) -> InterpretterResult<u64> {
+    Ok(u64::from_le_bytes(self.array()?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::Chip8Key;
+
+  const ROMS: [(&str, &[u8]); 7] = [
+    ("outlaw", include_bytes!("../../roms/games/outlaw.ch8")),
+    ("chip8-logo", include_bytes!("../../roms/test-suite/1-chip8-logo.ch8")),
+    ("ibm-logo", include_bytes!("../../roms/test-suite/2-ibm-logo.ch8")),
+    ("corax+", include_bytes!("../../roms/test-suite/3-corax+.ch8")),
+    ("flags", include_bytes!("../../roms/test-suite/4-flags.ch8")),
+    ("quirks", include_bytes!("../../roms/test-suite/5-quirks.ch8")),
+    ("keypad", include_bytes!("../../roms/test-suite/6-keypad.ch8")),
+  ];
+
+  fn run(chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+      chip8.run_frame().unwrap();
+    }
+  }
+
+  #[test]
+  fn test_crc32() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+  }
+
+  #[test]
+  fn test_round_trip_roms() {
+    for variant in [Variant::Chip8, Variant::SuperChip, Variant::XoChip] {
+      for (name, rom) in ROMS {
+        let mut chip8 = Chip8::with_variant(variant, variant.quirks());
+        chip8.set_seed(7);
+        chip8.load_rom(rom).unwrap();
+        chip8.set_key(Chip8Key::E, true);
+        run(&mut chip8, 30);
+        let state = chip8.save_state();
+
+        let mut restored = Chip8::default();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.save_state(), state, "{name} on {variant:?}");
+
+        run(&mut chip8, 30);
+        run(&mut restored, 30);
+        assert_eq!(restored.frame(), chip8.frame(), "{name} on {variant:?}");
+        assert_eq!(restored.save_state(), chip8.save_state(), "{name} on {variant:?}");
+      }
+    }
+  }
+
+  #[test]
+  fn test_round_trip_machine() {
+    let mut chip8 = Chip8::with_variant(Variant::XoChip, Quirks::schip_legacy());
+    chip8.set_cycles_per_frame(30);
+    chip8.rng.next_u64();
+    chip8.registers.push(0x202).unwrap();
+    chip8.registers.push(0x208).unwrap();
+    chip8.registers.keys[3] = true;
+    chip8.registers.released_keys[9] = true;
+    chip8.registers.key_wait = Some(KeyWait::Release(9));
+    chip8.registers.set_dt(12);
+    chip8.registers.set_st(34);
+    chip8.registers.rpl[2] = 5;
+    chip8.registers.pitch = 80;
+    chip8.frame_buffer.set_hires(true).unwrap();
+    chip8.frame_buffer.xor_xy(127, 63, 2).unwrap();
+    chip8.frame_buffer.set_planes(3);
+    chip8.memory.write_byte(0xFFFF, 0xAB).unwrap();
+
+    let mut restored = Chip8::default();
+    restored.load_state(&chip8.save_state()).unwrap();
+    assert_eq!(restored.variant(), Variant::XoChip);
+    assert_eq!(restored.quirks(), &Quirks::schip_legacy());
+    assert_eq!(restored.cycles_per_frame(), 30);
+    assert_eq!(restored.rng, chip8.rng);
+    assert_eq!(restored.registers.stack(), &[0x202, 0x208]);
+    assert_eq!(restored.registers.keys, chip8.registers.keys);
+    assert_eq!(restored.registers.released_keys, chip8.registers.released_keys);
+    assert_eq!(restored.registers.key_wait, Some(KeyWait::Release(9)));
+    assert_eq!(restored.registers.get_dt(), 12);
+    assert_eq!(restored.registers.get_st(), 34);
+    assert_eq!(restored.registers.rpl[2], 5);
+    assert_eq!(restored.pitch(), 80);
+    assert_eq!(restored.resolution(), (128, 64));
+    assert_eq!(restored.frame_buffer.planes(), 3);
+    assert_eq!(restored.frame()[128 * 64 - 1], 2);
+    assert_eq!(restored.memory.read_byte(0xFFFF).unwrap(), 0xAB);
+  }
+
+  #[test]
+  fn test_invalid_states() {
+    let mut chip8 = Chip8::with_seed(1);
+    chip8.load_rom(ROMS[0].1).unwrap();
+    let state = chip8.save_state();
+    let mut target = Chip8::default();
+
+    assert!(matches!(
+      target.load_state(b"not a save state"),
+      Err(InterpreterError::InvalidStateHeader)
+    ));
+
+    let mut bad_version = state.clone();
+    bad_version[4] = 2;
+    assert!(matches!(
+      target.load_state(&bad_version),
+      Err(InterpreterError::UnsupportedStateVersion(2))
+    ));
+
+    let mut bad_checksum = state.clone();
+    bad_checksum[HEADER_SIZE + 100] ^= 1;
+    assert!(matches!(
+      target.load_state(&bad_checksum),
+      Err(InterpreterError::StateChecksumMismatch)
+    ));
+
+    assert!(matches!(
+      target.load_state(&state[..state.len() - 1]),
+      Err(InterpreterError::CorruptState(_))
+    ));
+
+    // Fields that pass the checksum can still be out of range, so a state is
+    // resealed after it's been tampered with
+    let reseal = |mut state: Vec<u8>| {
+      let end = state.len() - CHECKSUM_SIZE;
+      let checksum = crc32(&state[..end]);
+      state[end..].copy_from_slice(&checksum.to_le_bytes());
+      state
+    };
+    // Cycles per frame come after the variant and quirks
+    let mut no_cycles = state.clone();
+    no_cycles[HEADER_SIZE + 2..HEADER_SIZE + 6].fill(0);
+    assert!(matches!(
+      target.load_state(&reseal(no_cycles)),
+      Err(InterpreterError::CorruptState(_))
+    ));
+    // The last pixel is just before the checksum
+    let mut bad_pixel = state.clone();
+    bad_pixel[state.len() - CHECKSUM_SIZE - 1] = 4;
+    assert!(matches!(
+      target.load_state(&reseal(bad_pixel)),
+      Err(InterpreterError::CorruptState(_))
+    ));
+    let mut good_pixel = state.clone();
+    good_pixel[state.len() - CHECKSUM_SIZE - 1] = 3;
+    Chip8::default().load_state(&reseal(good_pixel)).unwrap();
+
+    // Failed loads leave the machine alone
+    assert_eq!(target.registers.pc, 0x200);
+    assert_eq!(target.memory.read_byte(0x200).unwrap(), 0);
+  }
+}
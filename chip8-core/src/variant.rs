@@ -0,0 +1,31 @@
+use super::{
+  memory::{MEMORY_SIZE, XO_MEMORY_SIZE},
+  quirks::Quirks,
+};
+
+// The CHIP-8 dialect a ROM was written for. Each one extends the instruction
+// set of the one before it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variant {
+  #[default]
+  Chip8,
+  SuperChip,
+  XoChip,
+}
+
+impl Variant {
+  pub fn quirks(self) -> Quirks {
+    match self {
+      Variant::Chip8 => Quirks::vip(),
+      Variant::SuperChip => Quirks::schip_modern(),
+      Variant::XoChip => Quirks::xo_chip(),
+    }
+  }
+
+  pub fn memory_size(self) -> usize {
+    match self {
+      Variant::Chip8 | Variant::SuperChip => MEMORY_SIZE,
+      Variant::XoChip => XO_MEMORY_SIZE,
+    }
+  }
+}
@@ -0,0 +1,24 @@
+// Assembles every Octo source shipped in roms/ and checks it comes out byte
+// for byte the same as the .ch8 next to it
+#![cfg(feature = "alloc")]
+
+use chip8_core::assemble;
+use std::{fs, path::PathBuf};
+
+#[test]
+fn test_bundled_sources() {
+  let roms = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../roms");
+  let mut count = 0;
+  for dir in ["games", "test-suite"] {
+    for entry in fs::read_dir(roms.join(dir)).unwrap() {
+      let path = entry.unwrap().path();
+      if path.extension().is_some_and(|ext| ext == "8o") {
+        let source = fs::read_to_string(&path).unwrap();
+        let rom = assemble(&source).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+        assert!(rom == fs::read(path.with_extension("ch8")).unwrap(), "{} differs", path.display());
+        count += 1;
+      }
+    }
+  }
+  assert_eq!(count, 7);
+}
@@ -0,0 +1,95 @@
+// Runs the Timendus test suite ROMs in roms/test-suite under each quirk
+// profile and compares the final display with the golden images in
+// roms/test-suite/golden. Run with UPDATE_GOLDEN=1 to rewrite the images after
+// an intentional change, then check the new ones by eye.
+use chip8_core::{Chip8, Chip8Key, Quirks, Variant};
+use std::{env, fs, path::PathBuf};
+
+const FRAMES: u64 = 600;
+// With the display wait quirk the menus take a couple of seconds to draw, one
+// character per frame
+const MENU_FRAME: u64 = 120;
+
+struct Profile {
+  name: &'static str,
+  variant: Variant,
+  quirks: Quirks,
+  // The platform to pick in 5-quirks' menu
+  platform: usize,
+}
+
+const PROFILES: [Profile; 4] = [
+  Profile { name: "vip", variant: Variant::Chip8, quirks: Quirks::vip(), platform: 1 },
+  Profile { name: "schip-modern", variant: Variant::SuperChip, quirks: Quirks::schip_modern(), platform: 2 },
+  Profile { name: "schip-legacy", variant: Variant::SuperChip, quirks: Quirks::schip_legacy(), platform: 2 },
+  Profile { name: "xo-chip", variant: Variant::XoChip, quirks: Quirks::xo_chip(), platform: 3 },
+];
+
+// Frame, keypad digit and whether it's pressed
+type KeyScript = Vec<(u64, usize, bool)>;
+
+// Chooses a menu entry by tapping its number key
+fn menu_choice(choice: usize) -> KeyScript {
+  vec![(MENU_FRAME, choice, true), (MENU_FRAME + 5, choice, false)]
+}
+
+fn run(rom: &str, profile: &Profile, keys: KeyScript) {
+  let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../roms/test-suite");
+  let mut chip8 = Chip8::with_variant(profile.variant, profile.quirks);
+  chip8.set_seed(0);
+  chip8.load_rom(&fs::read(path.join(format!("{rom}.ch8"))).unwrap()).unwrap();
+  for frame in 0..FRAMES {
+    for &(_, key, pressed) in keys.iter().filter(|&&(at, _, _)| at == frame) {
+      chip8.set_key(Chip8Key::from_index(key).unwrap(), pressed);
+    }
+    chip8.run_frame().unwrap();
+  }
+
+  let actual = chip8.frame_buffer().to_string();
+  let golden = path.join("golden").join(format!("{rom}.{}.txt", profile.name));
+  if env::var_os("UPDATE_GOLDEN").is_some() {
+    fs::write(&golden, &actual).unwrap();
+    return;
+  }
+  let expected = fs::read_to_string(&golden)
+    .unwrap_or_else(|_| panic!("missing {}, run with UPDATE_GOLDEN=1", golden.display()));
+  assert!(
+    actual == expected,
+    "{rom} under {} doesn't match {}\nexpected:\n{expected}\nactual:\n{actual}",
+    profile.name,
+    golden.display(),
+  );
+}
+
+#[test]
+fn test_chip8_logo() {
+  PROFILES.iter().for_each(|profile| run("1-chip8-logo", profile, vec![]));
+}
+
+#[test]
+fn test_ibm_logo() {
+  PROFILES.iter().for_each(|profile| run("2-ibm-logo", profile, vec![]));
+}
+
+#[test]
+fn test_corax() {
+  PROFILES.iter().for_each(|profile| run("3-corax+", profile, vec![]));
+}
+
+#[test]
+fn test_flags() {
+  PROFILES.iter().for_each(|profile| run("4-flags", profile, vec![]));
+}
+
+#[test]
+fn test_quirks() {
+  PROFILES.iter().for_each(|profile| run("5-quirks", profile, menu_choice(profile.platform)));
+}
+
+#[test]
+fn test_keypad() {
+  // Picks the EX9E test and holds down 5, which should light up on the grid
+  let mut keys = menu_choice(1);
+  keys.push((MENU_FRAME + 60, 5, true));
+  PROFILES.iter().for_each(|profile| run("6-keypad", profile, keys.clone()));
+}
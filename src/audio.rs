@@ -0,0 +1,79 @@
+use chip8_core::audio::{AudioSink, Oscillator, Sound};
+use cpal::{
+  traits::{DeviceTrait, HostTrait, StreamTrait},
+  Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+};
+use log::error;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AudioError {
+  #[error("No audio output device available")]
+  NoDevice,
+  #[error("Unsupported sample format {0}")]
+  UnsupportedFormat(SampleFormat),
+  #[error("Audio config error: {0}")]
+  ConfigError(#[from] cpal::DefaultStreamConfigError),
+  #[error("Audio stream error: {0}")]
+  BuildStreamError(#[from] cpal::BuildStreamError),
+  #[error("Audio playback error: {0}")]
+  PlayStreamError(#[from] cpal::PlayStreamError),
+}
+
+// Plays sound on the default output device. The stream pulls samples on its
+// own thread, so play just hands it the latest sound state.
+pub struct DeviceSink {
+  sound: Arc<Mutex<Sound>>,
+  _stream: Stream,
+}
+
+impl DeviceSink {
+  pub fn new() -> Result<Self, AudioError> {
+    let device = cpal::default_host()
+      .default_output_device()
+      .ok_or(AudioError::NoDevice)?;
+    let supported = device.default_output_config()?;
+    let config = supported.config();
+    let sound = Arc::new(Mutex::new(Sound::default()));
+    let stream = match supported.sample_format() {
+      SampleFormat::F32 => build_stream::<f32>(&device, &config, sound.clone())?,
+      SampleFormat::I16 => build_stream::<i16>(&device, &config, sound.clone())?,
+      SampleFormat::U16 => build_stream::<u16>(&device, &config, sound.clone())?,
+      format => return Err(AudioError::UnsupportedFormat(format)),
+    };
+    stream.play()?;
+    Ok(Self {
+      sound,
+      _stream: stream,
+    })
+  }
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(
+  device: &Device,
+  config: &StreamConfig,
+  sound: Arc<Mutex<Sound>>,
+) -> Result<Stream, AudioError> {
+  let channels = config.channels as usize;
+  let mut oscillator = Oscillator::new(config.sample_rate.0);
+  let stream = device.build_output_stream(
+    config,
+    move |data: &mut [T], _| {
+      let sound = *sound.lock().unwrap();
+      for frame in data.chunks_mut(channels) {
+        let sample = T::from_sample(oscillator.next_sample(&sound));
+        frame.iter_mut().for_each(|channel| *channel = sample);
+      }
+    },
+    |err| error!("{err}"),
+    None,
+  )?;
+  Ok(stream)
+}
+
+impl AudioSink for DeviceSink {
+  fn play(&mut self, sound: &Sound) {
+    *self.sound.lock().unwrap() = *sound;
+  }
+}
@@ -0,0 +1,287 @@
+use crate::{
+  config::{Config, ConfigFile, QuirkProfile, Settings, MAX_SCALE},
+  error::Chip8Error,
+  read_rom,
+  rom_sha1,
+  rom_variant,
+  romdb,
+};
+use chip8_core::{Chip8, Variant};
+use clap::{Args, Parser, Subcommand};
+use log::info;
+use std::path::{Path, PathBuf};
+
+const VARIANT_HELP: &str = "Instruction set to run: chip8, schip or xo-chip [default: from the ROM]";
+
+#[derive(Debug, Parser)]
+#[command(
+  version,
+  about = "CHIP-8, SUPER-CHIP and XO-CHIP interpreter",
+  args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+  #[command(subcommand)]
+  pub command: Option<Command>,
+  #[arg(help = "ROM to run, a file dialog asks for one when omitted")]
+  pub rom: Option<PathBuf>,
+  #[arg(long, value_name = "PATH", help = "Config file to use instead of the user's config.toml")]
+  pub config: Option<PathBuf>,
+  #[arg(long, help = "Instructions run per second")]
+  pub ips: Option<usize>,
+  #[arg(
+    long,
+    value_parser = clap::value_parser!(u32).range(1..=MAX_SCALE as i64),
+    help = "Window pixels per hires pixel, lores pixels are twice the size",
+  )]
+  pub scale: Option<u32>,
+  #[arg(long, value_enum, help = "Quirks to run with instead of the ROM variant's defaults")]
+  pub quirks: Option<QuirkProfile>,
+  #[arg(long, value_parser = parse_variant, help = VARIANT_HELP)]
+  pub variant: Option<Variant>,
+  #[arg(long, help = "Seed for the random number generator")]
+  pub seed: Option<u64>,
+  #[arg(long, help = "Start paused, P toggles pausing")]
+  pub paused: bool,
+  #[arg(long, requires = "rom", help = "Run without a window and print the final state")]
+  pub headless: bool,
+  #[arg(long, requires = "headless", conflicts_with = "cycles", help = "Frames to run headless [default: 600]")]
+  pub frames: Option<u64>,
+  #[arg(long, requires = "headless", help = "Instructions to run headless, rounded up to a frame")]
+  pub cycles: Option<u64>,
+  #[arg(
+    long,
+    requires = "headless",
+    value_name = "FRAME:KEY+,FRAME:KEY-,...",
+    help = "Key presses (+) and releases (-) to script headless",
+  )]
+  pub keys: Option<String>,
+  #[arg(long, requires = "headless", help = "Write the final display to a PNG image")]
+  pub png: Option<PathBuf>,
+  #[arg(long, requires = "headless", help = "Print the final display as ASCII art")]
+  pub ascii: bool,
+  #[arg(long, requires = "headless", help = "Record the sound to a WAV file")]
+  pub wav: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+  #[command(about = "List a ROM's instructions")]
+  Disasm(DisasmArgs),
+  #[command(about = "Step through a ROM from a command prompt on stdin, without a window")]
+  Debug(DebugArgs),
+  #[command(about = "Serve a ROM to GDB over the remote serial protocol on localhost")]
+  Gdb(GdbArgs),
+  #[command(about = "Serve the Debug Adapter Protocol on stdin and stdout, for editors to launch ROMs with")]
+  Dap,
+}
+
+#[derive(Debug, Args)]
+pub struct DisasmArgs {
+  #[arg(help = "ROM to disassemble")]
+  pub rom: PathBuf,
+  #[arg(long, help = "Write Octo syntax instead of mnemonics")]
+  pub octo: bool,
+  #[arg(
+    long,
+    value_parser = parse_variant,
+    help = "Instruction set to decode with: chip8, schip or xo-chip [default: from the ROM]",
+  )]
+  pub variant: Option<Variant>,
+}
+
+#[derive(Debug, Args)]
+pub struct DebugArgs {
+  #[arg(help = "ROM to debug")]
+  pub rom: PathBuf,
+  #[arg(long, value_name = "PATH", help = "Config file to use instead of the user's config.toml")]
+  pub config: Option<PathBuf>,
+  #[arg(long, help = "Instructions run per second")]
+  pub ips: Option<usize>,
+  #[arg(long, value_enum, help = "Quirks to run with instead of the ROM variant's defaults")]
+  pub quirks: Option<QuirkProfile>,
+  #[arg(long, value_parser = parse_variant, help = VARIANT_HELP)]
+  pub variant: Option<Variant>,
+  #[arg(long, help = "Seed for the random number generator")]
+  pub seed: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct GdbArgs {
+  #[command(flatten)]
+  pub machine: DebugArgs,
+  #[arg(long, default_value_t = 1234, help = "Port to listen on, for target remote :PORT")]
+  pub port: u16,
+}
+
+impl DebugArgs {
+  pub fn load(&self) -> Result<(Chip8, Config), Chip8Error> {
+    self.load_rom(&read_rom(&self.rom)?)
+  }
+
+  // For a ROM that's already been read, e.g. assembled along with symbols
+  pub fn load_rom(&self, rom: &[u8]) -> Result<(Chip8, Config), Chip8Error> {
+    let settings = Settings {
+      ips: self.ips,
+      quirks: self.quirks,
+      variant: self.variant,
+      ..Settings::default()
+    };
+    load(&self.rom, rom, self.config.as_deref(), &settings, self.seed)
+  }
+}
+
+pub fn parse_variant(name: &str) -> Result<Variant, String> {
+  match name {
+    "chip8" => Ok(Variant::Chip8),
+    "schip" => Ok(Variant::SuperChip),
+    "xo-chip" => Ok(Variant::XoChip),
+    _ => Err(format!("expected chip8, schip or xo-chip, found {name}")),
+  }
+}
+
+impl Cli {
+  // The flags that can also be set in the config file, as the top layer
+  pub fn settings(&self) -> Settings {
+    Settings {
+      ips: self.ips,
+      scale: self.scale,
+      quirks: self.quirks,
+      variant: self.variant,
+      ..Settings::default()
+    }
+  }
+
+  pub fn load(&self, path: &Path) -> Result<(Chip8, Config), Chip8Error> {
+    load(path, &read_rom(path)?, self.config.as_deref(), &self.settings(), self.seed)
+  }
+}
+
+// Sets up a machine for the ROM read from path, with the settings from the
+// config file and ROM database for it and the flags on top
+fn load(
+  path: &Path,
+  rom: &[u8],
+  config_file: Option<&Path>,
+  flags: &Settings,
+  seed: Option<u64>,
+) -> Result<(Chip8, Config), Chip8Error> {
+  let hash = rom_sha1(rom);
+  let info = romdb::lookup(&hash);
+  if let Some(info) = info {
+    info!("Recognised {} ({hash})", info.title);
+  }
+  let mut config = ConfigFile::load(config_file)?.config_for(&hash, info)?;
+  config.apply(flags, "command line")?;
+
+  let variant = config.variant.unwrap_or_else(|| rom_variant(path));
+  let quirks = config.quirks.unwrap_or_else(|| variant.quirks());
+  let mut chip8 = Chip8::with_variant(variant, quirks);
+  chip8.set_instructions_per_second(config.ips);
+  if let Some(seed) = seed {
+    chip8.set_seed(seed);
+  }
+  chip8.load_rom(rom)?;
+  Ok((chip8, config))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &str) -> Result<Cli, clap::Error> {
+    Cli::try_parse_from(std::iter::once("chip8rs").chain(args.split_whitespace()))
+  }
+
+  #[test]
+  fn test_parse() {
+    let cli = parse("").unwrap();
+    assert_eq!(cli.rom, None);
+    assert_eq!(cli.ips, None);
+    assert_eq!(cli.scale, None);
+    assert!(!cli.paused && !cli.headless);
+
+    let cli = parse("rom.ch8 --ips 1000 --scale 4 --quirks schip-legacy --seed 7 --paused").unwrap();
+    assert_eq!(cli.rom, Some(PathBuf::from("rom.ch8")));
+    assert_eq!(cli.ips, Some(1000));
+    assert_eq!(cli.scale, Some(4));
+    assert_eq!(cli.quirks, Some(QuirkProfile::SchipLegacy));
+    assert_eq!(cli.seed, Some(7));
+    assert!(cli.paused);
+
+    assert!(parse("--scale 0").is_err());
+    assert!(parse("--quirks bogus").is_err());
+    assert!(parse("rom.ch8 --bogus").is_err());
+  }
+
+  #[test]
+  fn test_parse_headless() {
+    let cli = parse("rom.ch8 --headless --frames 10 --ascii --png out.png --wav out.wav").unwrap();
+    assert!(cli.headless && cli.ascii);
+    assert_eq!(cli.frames, Some(10));
+    assert_eq!(cli.png, Some(PathBuf::from("out.png")));
+    assert_eq!(cli.wav, Some(PathBuf::from("out.wav")));
+    assert!(parse("rom.ch8 --wav out.wav").is_err());
+    assert!(parse("--headless").is_err());
+    assert!(parse("rom.ch8 --frames 10").is_err());
+    assert!(parse("rom.ch8 --headless --frames").is_err());
+    assert!(parse("rom.ch8 --headless --frames 1 --cycles 1").is_err());
+  }
+
+  #[test]
+  fn test_parse_disasm() {
+    let cli = parse("disasm rom.ch8 --octo --variant xo-chip").unwrap();
+    let Some(Command::Disasm(args)) = cli.command else {
+      panic!("expected the disasm subcommand");
+    };
+    assert_eq!(args.rom, PathBuf::from("rom.ch8"));
+    assert!(args.octo);
+    assert_eq!(args.variant, Some(Variant::XoChip));
+    assert!(parse("disasm").is_err());
+    assert!(parse("disasm rom.ch8 --variant vip").is_err());
+    assert!(parse("--headless disasm rom.ch8").is_err());
+  }
+
+  #[test]
+  fn test_parse_debug() {
+    let cli = parse("debug rom.ch8 --quirks vip --variant schip --seed 3").unwrap();
+    let Some(Command::Debug(args)) = cli.command else {
+      panic!("expected the debug subcommand");
+    };
+    assert_eq!(args.rom, PathBuf::from("rom.ch8"));
+    assert_eq!(args.quirks, Some(QuirkProfile::Vip));
+    assert_eq!(args.variant, Some(Variant::SuperChip));
+    assert_eq!(args.seed, Some(3));
+    assert!(parse("debug").is_err());
+    assert!(parse("debug rom.ch8 --headless").is_err());
+
+    let cli = parse("gdb rom.ch8 --ips 600").unwrap();
+    let Some(Command::Gdb(args)) = cli.command else {
+      panic!("expected the gdb subcommand");
+    };
+    assert_eq!(args.machine.ips, Some(600));
+    assert_eq!(args.port, 1234);
+    assert!(parse("gdb rom.ch8 --port 9000").is_ok());
+    assert!(parse("gdb rom.ch8 --port 70000").is_err());
+  }
+
+  #[test]
+  fn test_load() {
+    let path = std::env::temp_dir().join(format!("chip8rs-cli-{}.toml", std::process::id()));
+    std::fs::write(&path, "ips = 600\nscale = 3\nquirks = \"xo-chip\"").unwrap();
+    let cli = parse(&format!("--config {} --ips 120 --quirks vip", path.display())).unwrap();
+    let result = cli.load(Path::new("roms/test-suite/2-ibm-logo.ch8"));
+    let (chip8, config) = result.unwrap();
+    assert_eq!(chip8.cycles_per_frame(), 2);
+    assert_eq!(*chip8.quirks(), QuirkProfile::Vip.quirks());
+    assert_eq!(config.scale, 3);
+
+    // The flag wins over the ROM database, and the quirks follow the variant
+    std::fs::write(&path, "ips = 600").unwrap();
+    let cli = parse(&format!("--config {} --variant schip", path.display())).unwrap();
+    let result = cli.load(Path::new("roms/test-suite/2-ibm-logo.ch8"));
+    std::fs::remove_file(&path).unwrap();
+    let (chip8, _) = result.unwrap();
+    assert_eq!(chip8.variant(), Variant::SuperChip);
+    assert_eq!(*chip8.quirks(), Variant::SuperChip.quirks());
+  }
+}
@@ -0,0 +1,411 @@
+use crate::{keymap::KEYMAP, romdb::RomInfo};
+use chip8_core::{Chip8Key, Quirks, Variant, FRAMES_PER_SECOND, INSTRUCTIONS_PER_SECOND};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::{
+  collections::BTreeMap,
+  fs,
+  io::{self, ErrorKind},
+  path::{Path, PathBuf},
+};
+use thiserror::Error;
+use winit::event::VirtualKeyCode;
+
+// Relative to the platform's config directory, e.g. ~/.config on Linux
+const CONFIG_FILE: &str = "chip8rs/config.toml";
+pub const DEFAULT_SCALE: u32 = 5;
+pub const MAX_SCALE: u32 = 64;
+const MAX_INSTRUCTIONS_PER_SECOND: usize = 1_000_000;
+
+// Colours for each combination of the two XO-CHIP bitplanes
+pub const PALETTE: [[u8; 4]; 4] = [
+  [0x00, 0x00, 0x00, 0xFF],
+  [0xFF, 0xFF, 0xFF, 0xFF],
+  [0xAA, 0xAA, 0xAA, 0xFF],
+  [0x55, 0x55, 0x55, 0xFF],
+];
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+  #[error("Unable to read {}: {source}", path.display())]
+  Read { path: PathBuf, source: io::Error },
+  #[error("Invalid config file {}: {source}", path.display())]
+  Parse { path: PathBuf, source: toml::de::Error },
+  #[error("Invalid {setting} in {origin}: {message}")]
+  Invalid {
+    origin: String,
+    setting: &'static str,
+    message: String,
+  },
+}
+
+// Named sets of quirks, matching the platforms of the Timendus quirks test
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuirkProfile {
+  Vip,
+  SchipModern,
+  SchipLegacy,
+  XoChip,
+}
+
+impl QuirkProfile {
+  pub fn quirks(self) -> Quirks {
+    match self {
+      QuirkProfile::Vip => Quirks::vip(),
+      QuirkProfile::SchipModern => Quirks::schip_modern(),
+      QuirkProfile::SchipLegacy => Quirks::schip_legacy(),
+      QuirkProfile::XoChip => Quirks::xo_chip(),
+    }
+  }
+}
+
+// An RGB colour written as "#rrggbb"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(pub [u8; 4]);
+
+impl TryFrom<String> for Color {
+  type Error = String;
+
+  fn try_from(value: String) -> Result<Self, String> {
+    value
+      .strip_prefix('#')
+      .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+      .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+      .map(|rgb| {
+        let [_, r, g, b] = rgb.to_be_bytes();
+        Color([r, g, b, 0xFF])
+      })
+      .ok_or_else(|| format!("expected a colour like \"#ff8800\", found \"{value}\""))
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Colors {
+  pub background: Option<Color>,
+  pub foreground: Option<Color>,
+  // XO-CHIP pixels lit only in the second plane, and in both planes
+  pub second_plane: Option<Color>,
+  pub both_planes: Option<Color>,
+}
+
+// One layer of settings as written in the config file. Anything left out
+// falls through to the layer below.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+  pub ips: Option<usize>,
+  pub scale: Option<u32>,
+  pub quirks: Option<QuirkProfile>,
+  // Only set from the command line, the config file goes by the ROM
+  #[serde(skip)]
+  pub variant: Option<Variant>,
+  #[serde(default)]
+  pub colors: Colors,
+  // Keypad digit to keyboard key, e.g. `A = "Z"`. Keys are named after
+  // winit's VirtualKeyCode.
+  #[serde(default)]
+  pub keys: BTreeMap<String, VirtualKeyCode>,
+  // Overrides for a single ROM, keyed by the SHA-1 of its contents. Only
+  // allowed at the top level.
+  #[serde(default)]
+  pub roms: BTreeMap<String, Settings>,
+}
+
+// The settings a ROM runs with once every layer has been applied
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+  pub ips: usize,
+  pub scale: u32,
+  // None picks the variant from the file extension
+  pub variant: Option<Variant>,
+  // None runs with the defaults of the variant
+  pub quirks: Option<Quirks>,
+  pub palette: [[u8; 4]; 4],
+  // A keypad key can have several bindings
+  pub keymap: Vec<(VirtualKeyCode, Chip8Key)>,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      ips: INSTRUCTIONS_PER_SECOND,
+      scale: DEFAULT_SCALE,
+      variant: None,
+      quirks: None,
+      palette: PALETTE,
+      keymap: KEYMAP.to_vec(),
+    }
+  }
+}
+
+impl Config {
+  // Applies a layer on top of this one. `origin` says where the layer came
+  // from in error messages.
+  pub fn apply(&mut self, settings: &Settings, origin: &str) -> Result<(), ConfigError> {
+    let invalid = |setting, message| ConfigError::Invalid {
+      origin: origin.to_string(),
+      setting,
+      message,
+    };
+    if let Some(ips) = settings.ips {
+      if !(1..=MAX_INSTRUCTIONS_PER_SECOND).contains(&ips) {
+        let message = format!("{ips} isn't between 1 and {MAX_INSTRUCTIONS_PER_SECOND}");
+        return Err(invalid("ips", message));
+      }
+      self.ips = ips;
+    }
+    if let Some(scale) = settings.scale {
+      if !(1..=MAX_SCALE).contains(&scale) {
+        return Err(invalid("scale", format!("{scale} isn't between 1 and {MAX_SCALE}")));
+      }
+      self.scale = scale;
+    }
+    if let Some(variant) = settings.variant {
+      // Quirks picked for another variant don't carry over
+      if self.variant != Some(variant) {
+        self.quirks = None;
+      }
+      self.variant = Some(variant);
+    }
+    if let Some(profile) = settings.quirks {
+      self.quirks = Some(profile.quirks());
+    }
+
+    let Colors { background, foreground, second_plane, both_planes } = &settings.colors;
+    for (index, color) in [background, foreground, second_plane, both_planes].into_iter().enumerate() {
+      if let Some(Color(color)) = color {
+        self.palette[index] = *color;
+      }
+    }
+
+    for (digit, &virtual_key) in &settings.keys {
+      let key = u8::from_str_radix(digit, 16)
+        .ok()
+        .filter(|_| digit.len() == 1)
+        .and_then(|index| Chip8Key::from_index(index as usize))
+        .ok_or_else(|| invalid("keys", format!("\"{digit}\" isn't a keypad key, expected 0-9 or A-F")))?;
+      self.keymap.retain(|(_, bound)| *bound != key);
+      self.keymap.push((virtual_key, key));
+    }
+    for (index, (virtual_key, key)) in self.keymap.iter().enumerate() {
+      if let Some((_, other)) = self.keymap[index + 1..].iter().find(|(other, _)| other == virtual_key) {
+        let message = format!("{virtual_key:?} is bound to both {:X} and {:X}", key.index(), other.index());
+        return Err(invalid("keys", message));
+      }
+    }
+    Ok(())
+  }
+
+  // Applies the settings a ROM from the database is known to work with.
+  // Action bindings only go on keyboard keys that are still free.
+  pub fn apply_rom_info(&mut self, info: &RomInfo) {
+    self.variant = Some(info.variant);
+    self.quirks = Some(info.quirks);
+    if let Some(tickrate) = info.tickrate {
+      self.ips = tickrate * FRAMES_PER_SECOND as usize;
+    }
+    for (color, &rom_color) in self.palette.iter_mut().zip(info.colors) {
+      *color = rom_color;
+    }
+    for (virtual_key, key) in info.keymap() {
+      if self.keymap.iter().all(|(bound, _)| *bound != virtual_key) {
+        self.keymap.push((virtual_key, key));
+      }
+    }
+  }
+}
+
+// The user-level config file, with its per-ROM overrides
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigFile {
+  // Where the file was read from, for error messages
+  origin: String,
+  settings: Settings,
+}
+
+impl ConfigFile {
+  pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_FILE))
+  }
+
+  // Reads the file at path, or the default path when none is given. Only the
+  // default file is allowed to be missing.
+  pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+    let (path, required) = match path {
+      Some(path) => (path.to_path_buf(), true),
+      None => match Self::default_path() {
+        Some(path) => (path, false),
+        None => return Ok(Self::default()),
+      },
+    };
+    match fs::read_to_string(&path) {
+      Ok(text) => Self::parse(&text, &path.display().to_string()),
+      Err(err) if !required && err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+      Err(source) => Err(ConfigError::Read { path, source }),
+    }
+  }
+
+  // Every layer is checked up front so mistakes in an override show up
+  // whichever ROM is loaded
+  pub fn parse(text: &str, origin: &str) -> Result<Self, ConfigError> {
+    let mut settings: Settings = toml::from_str(text).map_err(|source| ConfigError::Parse {
+      path: PathBuf::from(origin),
+      source,
+    })?;
+    Config::default().apply(&settings, origin)?;
+    for (hash, rom) in std::mem::take(&mut settings.roms) {
+      let origin = format!("[roms.{hash}] of {origin}");
+      let invalid = |message: &str| ConfigError::Invalid {
+        origin: origin.clone(),
+        setting: "roms",
+        message: message.to_string(),
+      };
+      if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid("ROMs are keyed by the 40 digit SHA-1 of their contents"));
+      }
+      if !rom.roms.is_empty() {
+        return Err(invalid("ROM overrides can't be nested"));
+      }
+      Config::default().apply(&rom, &origin)?;
+      settings.roms.insert(hash.to_ascii_lowercase(), rom);
+    }
+    Ok(Self {
+      origin: origin.to_string(),
+      settings,
+    })
+  }
+
+  // The top-level settings, then what the ROM database knows about the ROM,
+  // then the override for it if there is one. The hash is the ROM's SHA-1.
+  pub fn config_for(&self, hash: &str, info: Option<&RomInfo>) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    config.apply(&self.settings, &self.origin)?;
+    if let Some(info) = info {
+      config.apply_rom_info(info);
+    }
+    if let Some(settings) = self.settings.roms.get(hash) {
+      config.apply(settings, &format!("[roms.{hash}] of {}", self.origin))?;
+    }
+    Ok(config)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rom_sha1;
+
+  const ROM: [u8; 2] = [0x12, 0x00];
+
+  fn parse(text: &str) -> Result<ConfigFile, ConfigError> {
+    ConfigFile::parse(text, "config.toml")
+  }
+
+  fn error(text: &str) -> String {
+    parse(text).unwrap_err().to_string()
+  }
+
+  #[test]
+  fn test_empty() {
+    let config = parse("").unwrap().config_for(&rom_sha1(&ROM), None).unwrap();
+    assert_eq!(config, Config::default());
+  }
+
+  #[test]
+  fn test_settings() {
+    let file = parse(
+      r##"
+        ips = 1000
+        quirks = "schip-legacy"
+
+        [colors]
+        foreground = "#FF8800"
+
+        [keys]
+        0 = "Space"
+        a = "K"
+      "##,
+    );
+    let config = file.unwrap().config_for(&rom_sha1(&ROM), None).unwrap();
+    assert_eq!(config.ips, 1000);
+    assert_eq!(config.scale, DEFAULT_SCALE);
+    assert_eq!(config.quirks, Some(Quirks::schip_legacy()));
+    assert_eq!(config.palette[0], PALETTE[0]);
+    assert_eq!(config.palette[1], [0xFF, 0x88, 0x00, 0xFF]);
+    assert!(config.keymap.contains(&(VirtualKeyCode::Space, Chip8Key::X)));
+    assert!(config.keymap.contains(&(VirtualKeyCode::K, Chip8Key::Z)));
+  }
+
+  #[test]
+  fn test_rom_override() {
+    let text = format!(
+      "scale = 4\nips = 500\n[roms.{}]\nips = 2000\n[roms.{}]\nips = 9\n",
+      rom_sha1(&ROM).to_ascii_uppercase(),
+      "0".repeat(40),
+    );
+    let file = parse(&text).unwrap();
+    let config = file.config_for(&rom_sha1(&ROM), None).unwrap();
+    assert_eq!((config.ips, config.scale), (2000, 4));
+    let config = file.config_for(&rom_sha1(&[0x00, 0xE0]), None).unwrap();
+    assert_eq!((config.ips, config.scale), (500, 4));
+  }
+
+  #[test]
+  fn test_rom_info() {
+    let info = RomInfo {
+      title: "Test",
+      variant: Variant::SuperChip,
+      quirks: Quirks::schip_legacy(),
+      tickrate: Some(30),
+      colors: &[[0x11, 0x22, 0x33, 0xFF]],
+      keys: &[("up", 5), ("a", 6), ("player2Up", 1)],
+    };
+    let hash = rom_sha1(&ROM);
+    let text = format!("ips = 500\n[keys]\n1 = \"Space\"\n[roms.{hash}]\nquirks = \"vip\"\n");
+    let config = parse(&text).unwrap().config_for(&hash, Some(&info)).unwrap();
+    assert_eq!(config.ips, 1800);
+    assert_eq!(config.variant, Some(Variant::SuperChip));
+    assert_eq!(config.quirks, Some(Quirks::vip()));
+    assert_eq!(config.palette[0], [0x11, 0x22, 0x33, 0xFF]);
+    assert_eq!(config.palette[1], PALETTE[1]);
+    assert!(config.keymap.contains(&(VirtualKeyCode::Up, Chip8Key::W)));
+    assert!(config.keymap.contains(&(VirtualKeyCode::Space, Chip8Key::One)));
+    assert_eq!(config.keymap.len(), 17);
+  }
+
+  #[test]
+  fn test_invalid() {
+    assert!(error("ipss = 10").contains("unknown field `ipss`"));
+    assert!(error("quirks = \"cosmac\"").contains("unknown variant `cosmac`"));
+    assert!(error("[colors]\nbackground = \"#12345\"").contains("expected a colour"));
+    assert!(error("[keys]\n0 = \"NotAKey\"").contains("NotAKey"));
+    assert_eq!(error("ips = 0"), "Invalid ips in config.toml: 0 isn't between 1 and 1000000");
+    assert_eq!(error("scale = 65"), "Invalid scale in config.toml: 65 isn't between 1 and 64");
+    assert_eq!(
+      error("[keys]\n10 = \"Z\""),
+      "Invalid keys in config.toml: \"10\" isn't a keypad key, expected 0-9 or A-F"
+    );
+    assert_eq!(
+      error("[keys]\n0 = \"Key1\""),
+      "Invalid keys in config.toml: Key1 is bound to both 1 and 0"
+    );
+    assert!(error("[roms.abc]\nips = 10").contains("40 digit SHA-1"));
+    let nested = format!("[roms.{0}.roms.{0}]\nips = 10", "0".repeat(40));
+    assert!(error(&nested).contains("can't be nested"));
+    let override_error = error(&format!("[roms.{}]\nscale = 0", "0".repeat(40)));
+    assert!(override_error.starts_with("Invalid scale in [roms.0000"));
+  }
+
+  #[test]
+  fn test_load() {
+    let path = std::env::temp_dir().join(format!("chip8rs-config-{}.toml", std::process::id()));
+    assert!(matches!(ConfigFile::load(Some(&path)), Err(ConfigError::Read { .. })));
+    fs::write(&path, "scale = [").unwrap();
+    let err = ConfigFile::load(Some(&path)).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(err, ConfigError::Parse { path: ref err_path, .. } if *err_path == path));
+  }
+}
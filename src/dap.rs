@@ -0,0 +1,713 @@
+use crate::{
+  cli::{parse_variant, DebugArgs},
+  config::QuirkProfile,
+  error::Chip8Error,
+  read_rom,
+};
+use chip8_core::{assemble_with_symbols, Chip8, Condition, Debugger, Disassembler, Stop, Symbols};
+use log::error;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+  fs,
+  io::{self, BufRead, BufReader, ErrorKind, Write},
+  path::{Path, PathBuf},
+  sync::mpsc::{self, RecvTimeoutError},
+  thread,
+  time::{Duration, Instant},
+};
+
+// How long a running machine waits on the next request before running the
+// frames that came due, about one frame
+const POLL: Duration = Duration::from_millis(16);
+// CHIP-8 has one thread of execution
+const THREAD_ID: u64 = 1;
+const REGISTERS: u64 = 1;
+const STACK: u64 = 2;
+
+// Speaks the Debug Adapter Protocol over stdin and stdout, for editors to
+// launch and debug ROMs with. Logging goes to stderr, out of the protocol's
+// way.
+pub fn run() -> Result<(), Chip8Error> {
+  Ok(Adapter::new(io::stdout().lock()).serve(BufReader::new(io::stdin()))?)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LaunchArguments {
+  // A ROM, or an Octo source to get breakpoints by line
+  program: PathBuf,
+  #[serde(default)]
+  stop_on_entry: bool,
+  ips: Option<usize>,
+  quirks: Option<QuirkProfile>,
+  // chip8, schip or xo-chip, as on the command line. Octo sources don't say
+  // which they're written for.
+  variant: Option<String>,
+  seed: Option<u64>,
+}
+
+struct Adapter<W> {
+  out: W,
+  seq: u64,
+  session: Option<Session>,
+}
+
+// The launched program
+struct Session {
+  chip8: Chip8,
+  debugger: Debugger,
+  // Only Octo sources have these
+  source: Option<(PathBuf, Symbols)>,
+  stop_on_entry: bool,
+  // Breakpoints set on source lines and on addresses, each replaced as a
+  // whole by the editor
+  line_breakpoints: Vec<(u16, Option<Condition>)>,
+  instruction_breakpoints: Vec<(u16, Option<Condition>)>,
+  // When the machine last ran, to run it in real time
+  last: Instant,
+}
+
+type Response = Result<Value, String>;
+
+impl<W: Write> Adapter<W> {
+  fn new(out: W) -> Self {
+    Self { out, seq: 0, session: None }
+  }
+
+  // Handles requests until the editor disconnects or closes the input. The
+  // input is read on its own thread, so a running machine can be paused.
+  fn serve(mut self, input: impl BufRead + Send + 'static) -> io::Result<()> {
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+      let mut input = input;
+      loop {
+        match read_message(&mut input) {
+          Ok(Some(message)) => {
+            if sender.send(message).is_err() {
+              return;
+            }
+          }
+          Ok(None) => return,
+          Err(err) => {
+            error!("Failed to read a request: {err}");
+            return;
+          }
+        }
+      }
+    });
+
+    loop {
+      let running = self.session.as_ref().is_some_and(|session| !session.debugger.is_paused());
+      let request = match running {
+        true => match requests.recv_timeout(POLL) {
+          Ok(request) => Some(request),
+          Err(RecvTimeoutError::Timeout) => None,
+          Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        },
+        false => match requests.recv() {
+          Ok(request) => Some(request),
+          Err(_) => return Ok(()),
+        },
+      };
+      if let Some(request) = request {
+        if !self.request(&request)? {
+          return Ok(());
+        }
+      }
+      if let Some(session) = &mut self.session {
+        let now = Instant::now();
+        let stop = session.debugger.update(&mut session.chip8, &(now - session.last));
+        session.last = now;
+        if let Some(stop) = stop {
+          self.stopped(stop)?;
+        }
+      }
+    }
+  }
+
+  // Replies to a request, then sends whatever events follow from it. Returns
+  // false once the editor is done.
+  fn request(&mut self, request: &Value) -> io::Result<bool> {
+    let command = request["command"].as_str().unwrap_or_default();
+    let args = &request["arguments"];
+    let response = match command {
+      "initialize" => Ok(json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsDisassembleRequest": true,
+        "supportsTerminateRequest": true,
+      })),
+      "launch" => self.launch(args),
+      "disconnect" | "terminate" => Ok(Value::Null),
+      _ => match &mut self.session {
+        Some(session) => session.request(command, args),
+        None => Err(format!("Launch a program before {command}")),
+      },
+    };
+    let success = response.is_ok();
+    self.respond(request, response)?;
+    if !success {
+      return Ok(true);
+    }
+
+    match command {
+      // Breakpoints are only taken once there's a program to put them in
+      "launch" => self.event("initialized", Value::Null)?,
+      "configurationDone" => {
+        let session = self.session.as_mut().unwrap();
+        match session.stop_on_entry {
+          true => self.event("stopped", json!({ "reason": "entry", "threadId": THREAD_ID }))?,
+          false => {
+            session.debugger.resume();
+            self.run_frame()?;
+          }
+        }
+      }
+      // A step mostly finishes in the first frame, so run it straight away
+      // rather than after the next poll
+      "continue" | "next" | "stepIn" | "stepOut" => self.run_frame()?,
+      "pause" => self.event("stopped", json!({ "reason": "pause", "threadId": THREAD_ID }))?,
+      "disconnect" | "terminate" => return Ok(false),
+      _ => {}
+    }
+    Ok(true)
+  }
+
+  fn launch(&mut self, args: &Value) -> Response {
+    let args = LaunchArguments::deserialize(args).map_err(|err| format!("Invalid launch arguments: {err}"))?;
+    let machine = DebugArgs {
+      rom: args.program.clone(),
+      config: None,
+      ips: args.ips,
+      quirks: args.quirks,
+      variant: args.variant.as_deref().map(parse_variant).transpose()?,
+      seed: args.seed,
+    };
+    let (rom, source) = match args.program.extension().is_some_and(|ext| ext == "8o") {
+      true => {
+        let source = fs::read_to_string(&args.program).map_err(|err| err.to_string())?;
+        let (rom, symbols) = assemble_with_symbols(&source).map_err(|err| err.to_string())?;
+        (rom, Some((canonical(&args.program), symbols)))
+      }
+      false => (read_rom(&args.program).map_err(|err| err.to_string())?, None),
+    };
+    let (chip8, _) = machine.load_rom(&rom).map_err(|err| err.to_string())?;
+    let mut debugger = Debugger::new();
+    debugger.pause();
+    self.session = Some(Session {
+      chip8,
+      debugger,
+      source,
+      stop_on_entry: args.stop_on_entry,
+      line_breakpoints: Vec::new(),
+      instruction_breakpoints: Vec::new(),
+      last: Instant::now(),
+    });
+    Ok(Value::Null)
+  }
+
+  fn run_frame(&mut self) -> io::Result<()> {
+    let Some(session) = &mut self.session else {
+      return Ok(());
+    };
+    session.last = Instant::now();
+    match session.debugger.run_frame(&mut session.chip8) {
+      Some(stop) => self.stopped(stop),
+      None => Ok(()),
+    }
+  }
+
+  fn stopped(&mut self, stop: Stop) -> io::Result<()> {
+    let reason = match stop {
+      Stop::Step => "step",
+      Stop::Breakpoint(_) => "breakpoint",
+      Stop::Watchpoint { .. } => "data breakpoint",
+      Stop::Fault(_) => "exception",
+      Stop::Exited => {
+        self.event("exited", json!({ "exitCode": 0 }))?;
+        return self.event("terminated", Value::Null);
+      }
+    };
+    let body = json!({
+      "reason": reason,
+      "description": stop.to_string(),
+      "threadId": THREAD_ID,
+      "allThreadsStopped": true,
+    });
+    self.event("stopped", body)
+  }
+
+  fn respond(&mut self, request: &Value, response: Response) -> io::Result<()> {
+    let mut message = json!({
+      "type": "response",
+      "request_seq": request["seq"],
+      "command": request["command"],
+      "success": response.is_ok(),
+    });
+    match response {
+      Ok(Value::Null) => {}
+      Ok(body) => message["body"] = body,
+      Err(error) => message["message"] = error.into(),
+    }
+    self.send(message)
+  }
+
+  fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+    let mut message = json!({ "type": "event", "event": event });
+    if !body.is_null() {
+      message["body"] = body;
+    }
+    self.send(message)
+  }
+
+  fn send(&mut self, mut message: Value) -> io::Result<()> {
+    self.seq += 1;
+    message["seq"] = self.seq.into();
+    let body = message.to_string();
+    write!(self.out, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    self.out.flush()
+  }
+}
+
+impl Session {
+  // The requests that need a launched program
+  fn request(&mut self, command: &str, args: &Value) -> Response {
+    match command {
+      "configurationDone" => Ok(Value::Null),
+      "setBreakpoints" => self.set_breakpoints(args),
+      "setInstructionBreakpoints" => self.set_instruction_breakpoints(args),
+      "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+      "stackTrace" => {
+        // The stack holds the address of each CALL, innermost last
+        let callers = self.chip8.stack().iter().rev().copied();
+        let frames: Vec<Value> = [self.chip8.pc()]
+          .into_iter()
+          .chain(callers)
+          .enumerate()
+          .map(|(id, address)| self.stack_frame(id, address))
+          .collect();
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+      }
+      // Registers are the same whichever frame is picked
+      "scopes" => Ok(json!({ "scopes": [
+        { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+        { "name": "Stack", "variablesReference": STACK, "expensive": false },
+      ]})),
+      "variables" => {
+        let chip8 = &self.chip8;
+        let variables: Vec<(String, String)> = match args["variablesReference"].as_u64() {
+          Some(REGISTERS) => {
+            let v = chip8.v().iter().enumerate().map(|(index, value)| (format!("V{index:X}"), format!("{value:#04x}")));
+            v.chain([
+              ("I".to_string(), format!("{:#06x}", chip8.i())),
+              ("PC".to_string(), format!("{:#06x}", chip8.pc())),
+              ("DT".to_string(), format!("{:#04x}", chip8.delay_timer())),
+              ("ST".to_string(), format!("{:#04x}", chip8.sound_timer())),
+            ])
+            .collect()
+          }
+          // Innermost call first, like the call stack
+          Some(STACK) => {
+            let stack = chip8.stack().iter().rev().enumerate();
+            stack.map(|(depth, address)| (format!("#{depth}"), format!("{address:#06x}"))).collect()
+          }
+          _ => return Err("Unknown variables reference".to_string()),
+        };
+        let variables: Vec<Value> = variables
+          .into_iter()
+          .map(|(name, value)| json!({ "name": name, "value": value, "variablesReference": 0 }))
+          .collect();
+        Ok(json!({ "variables": variables }))
+      }
+      "disassemble" => self.disassemble(args),
+      "continue" => {
+        self.debugger.resume();
+        Ok(json!({ "allThreadsContinued": true }))
+      }
+      "next" => {
+        self.debugger.step_over(&self.chip8);
+        Ok(Value::Null)
+      }
+      "stepIn" => {
+        self.debugger.step();
+        Ok(Value::Null)
+      }
+      "stepOut" => {
+        self.debugger.step_out(&self.chip8);
+        Ok(Value::Null)
+      }
+      "pause" => {
+        self.debugger.pause();
+        Ok(Value::Null)
+      }
+      _ => Err(format!("Unsupported request {command}")),
+    }
+  }
+
+  // Lines map to the first instruction on them, or on the next line with any.
+  // Only the launched source has lines to map.
+  fn set_breakpoints(&mut self, args: &Value) -> Response {
+    let path = args["source"]["path"].as_str().map(|path| canonical(Path::new(path)));
+    let symbols = self.source.as_ref().filter(|(source, _)| Some(source) == path.as_ref()).map(|(_, symbols)| symbols);
+    let mut breakpoints = Vec::new();
+    let mut results = Vec::new();
+    for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+      let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+      let result = match symbols.and_then(|symbols| symbols.address(line)) {
+        None if symbols.is_none() => Err("Only the launched Octo source has lines to break on".to_string()),
+        None => Err("No code at or after this line".to_string()),
+        Some((address, line)) => condition(breakpoint).map(|condition| {
+          breakpoints.push((address, condition));
+          json!({ "verified": true, "line": line, "instructionReference": format!("{address:#06x}") })
+        }),
+      };
+      results.push(result.unwrap_or_else(|message| json!({ "verified": false, "line": line, "message": message })));
+    }
+    // Breakpoints in other files can't be hit, and mustn't clear the ones
+    // that can
+    if symbols.is_some() {
+      self.line_breakpoints = breakpoints;
+      self.sync_breakpoints();
+    }
+    Ok(json!({ "breakpoints": results }))
+  }
+
+  // Breakpoints on raw addresses, for ROMs without a source
+  fn set_instruction_breakpoints(&mut self, args: &Value) -> Response {
+    let mut breakpoints = Vec::new();
+    let mut results = Vec::new();
+    for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+      let address = breakpoint["instructionReference"]
+        .as_str()
+        .and_then(parse_address)
+        .map(|address| address.wrapping_add_signed(breakpoint["offset"].as_i64().unwrap_or_default() as i16));
+      let result = match address {
+        Some(address) => condition(breakpoint).map(|condition| {
+          breakpoints.push((address, condition));
+          json!({ "verified": true, "instructionReference": format!("{address:#06x}") })
+        }),
+        None => Err("Invalid instruction reference".to_string()),
+      };
+      results.push(result.unwrap_or_else(|message| json!({ "verified": false, "message": message })));
+    }
+    self.instruction_breakpoints = breakpoints;
+    self.sync_breakpoints();
+    Ok(json!({ "breakpoints": results }))
+  }
+
+  fn sync_breakpoints(&mut self) {
+    self.debugger.clear_breakpoints();
+    for (address, condition) in self.line_breakpoints.iter().chain(&self.instruction_breakpoints) {
+      self.debugger.add_breakpoint(*address, condition.clone());
+    }
+  }
+
+  fn stack_frame(&self, id: usize, address: u16) -> Value {
+    let symbols = self.source.as_ref().map(|(_, symbols)| symbols);
+    let name = match symbols.and_then(|symbols| symbols.label(address)) {
+      Some(label) => label.to_string(),
+      None => format!("{address:#06x}"),
+    };
+    let mut frame = json!({
+      "id": id,
+      "name": name,
+      "line": 0,
+      "column": 0,
+      "instructionPointerReference": format!("{address:#06x}"),
+    });
+    if let Some((path, symbols)) = &self.source {
+      if let Some(line) = symbols.line(address) {
+        frame["line"] = line.into();
+        frame["column"] = 1.into();
+        frame["source"] = source(path);
+      }
+    }
+    frame
+  }
+
+  // Instructions are taken as two bytes each to count back from the address,
+  // which only drifts around the four byte F000 NNNN
+  fn disassemble(&self, args: &Value) -> Response {
+    let address = args["memoryReference"].as_str().and_then(parse_address).ok_or("Invalid memory reference")?;
+    let offset = args["offset"].as_i64().unwrap_or_default();
+    let offset = offset + args["instructionOffset"].as_i64().unwrap_or_default() * 2;
+    let count = args["instructionCount"].as_u64().unwrap_or_default() as usize;
+    let memory = self.chip8.memory();
+    let start = (address as i64 + offset).clamp(0, memory.size() as i64) as usize;
+    let len = (count * 4).min(memory.size() - start);
+    let bytes = memory.peek(start, len).map_err(|err| err.to_string())?;
+    let instructions: Vec<Value> = Disassembler::new(bytes, self.chip8.variant())
+      .with_origin(start as u16)
+      .take(count)
+      .map(|line| {
+        let raw: String = line.bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+        let mut instruction = json!({
+          "address": format!("{:#06x}", line.address),
+          "instructionBytes": raw,
+          "instruction": line.text().to_string(),
+        });
+        if let Some((path, symbols)) = &self.source {
+          if let Some(line) = symbols.line(line.address) {
+            instruction["line"] = line.into();
+            instruction["location"] = source(path);
+          }
+          if let Some((name, _)) = symbols.labels.iter().find(|&(_, &at)| at == line.address) {
+            instruction["symbol"] = name.as_str().into();
+          }
+        }
+        instruction
+      })
+      .collect();
+    Ok(json!({ "instructions": instructions }))
+  }
+}
+
+fn condition(breakpoint: &Value) -> Result<Option<Condition>, String> {
+  match breakpoint["condition"].as_str().filter(|condition| !condition.trim().is_empty()) {
+    Some(condition) => condition.parse().map(Some).map_err(|err: chip8_core::ConditionError| err.to_string()),
+    None => Ok(None),
+  }
+}
+
+fn source(path: &Path) -> Value {
+  let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+  json!({ "name": name, "path": path })
+}
+
+// Sources are told apart by path, which the editor may spell differently
+fn canonical(path: &Path) -> PathBuf {
+  fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Hex with a 0x prefix, or decimal
+fn parse_address(text: &str) -> Option<u16> {
+  match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+    Some(hex) => u16::from_str_radix(hex, 16).ok(),
+    None => text.parse().ok(),
+  }
+}
+
+// Reads a message framed by a Content-Length header, or None at the end of
+// the input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+  let mut len = None;
+  loop {
+    let mut header = String::new();
+    if input.read_line(&mut header)? == 0 {
+      return Ok(None);
+    }
+    let header = header.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some(value) = header.strip_prefix("Content-Length:") {
+      len = value.trim().parse().ok();
+    }
+  }
+  let len = len.ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Missing Content-Length header"))?;
+  let mut body = vec![0; len];
+  input.read_exact(&mut body)?;
+  Ok(Some(serde_json::from_slice(&body)?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{
+    collections::VecDeque,
+    io::{PipeReader, PipeWriter},
+  };
+
+  const SOURCE: &str = "\
+: main
+  v0 := 5
+  sub
+  v1 := 2
+  loop again
+: sub
+  v0 += 1
+  return
+";
+
+  // An editor stand-in, talking to an adapter over pipes the way it would
+  // over stdio
+  struct Client {
+    reader: BufReader<PipeReader>,
+    writer: PipeWriter,
+    seq: u64,
+    events: VecDeque<Value>,
+  }
+
+  impl Client {
+    fn connect() -> (Self, thread::JoinHandle<()>) {
+      let (server_input, writer) = io::pipe().unwrap();
+      let (reader, server_output) = io::pipe().unwrap();
+      let server = thread::spawn(move || Adapter::new(server_output).serve(BufReader::new(server_input)).unwrap());
+      (Self { reader: BufReader::new(reader), writer, seq: 0, events: VecDeque::new() }, server)
+    }
+
+    // Sends a request and waits for its response, keeping events for later
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+      self.seq += 1;
+      let body = json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments }).to_string();
+      write!(self.writer, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+      loop {
+        let message = read_message(&mut self.reader).unwrap().unwrap();
+        if message["type"] == "event" {
+          self.events.push_back(message);
+        } else {
+          assert_eq!(message["request_seq"], self.seq);
+          return message;
+        }
+      }
+    }
+
+    fn body(&mut self, command: &str, arguments: Value) -> Value {
+      let response = self.request(command, arguments);
+      assert_eq!(response["success"], true, "{command} failed: {response}");
+      response["body"].clone()
+    }
+
+    fn event(&mut self) -> Value {
+      match self.events.pop_front() {
+        Some(event) => event,
+        None => read_message(&mut self.reader).unwrap().unwrap(),
+      }
+    }
+
+    fn stopped(&mut self) -> String {
+      let event = self.event();
+      assert_eq!(event["event"], "stopped", "{event}");
+      event["body"]["reason"].as_str().unwrap().to_string()
+    }
+
+    // Line and name of each frame
+    fn stack(&mut self) -> Vec<(u64, String)> {
+      let body = self.body("stackTrace", json!({ "threadId": THREAD_ID }));
+      let frames = body["stackFrames"].as_array().unwrap().iter();
+      frames.map(|frame| (frame["line"].as_u64().unwrap(), frame["name"].as_str().unwrap().to_string())).collect()
+    }
+  }
+
+  #[test]
+  fn test_session() {
+    let path = std::env::temp_dir().join(format!("chip8rs-dap-{}.8o", std::process::id()));
+    fs::write(&path, SOURCE).unwrap();
+    let (mut client, server) = Client::connect();
+
+    assert_eq!(client.request("threads", json!({}))["success"], false);
+    assert_eq!(client.body("initialize", json!({ "adapterID": "chip8rs" }))["supportsConfigurationDoneRequest"], true);
+    let response = client.request("launch", json!({ "program": "missing.ch8" }));
+    assert_eq!(response["success"], false);
+    client.body("launch", json!({ "program": path, "stopOnEntry": true }));
+    assert_eq!(client.event()["event"], "initialized");
+
+    let body = client.body(
+      "setBreakpoints",
+      json!({
+        "source": { "path": path },
+        "breakpoints": [{ "line": 6, "condition": "V0 == 5" }, { "line": 4, "condition": "V0 ==" }, { "line": 20 }],
+      }),
+    );
+    let breakpoints = body["breakpoints"].as_array().unwrap();
+    // The label has no code, so the breakpoint moves to the line after
+    assert_eq!(breakpoints[0]["line"], 7);
+    assert_eq!(breakpoints[0]["instructionReference"], "0x0208");
+    assert_eq!(breakpoints[1]["verified"], false);
+    assert_eq!(breakpoints[2]["verified"], false);
+    let other = json!({ "source": { "path": "other.8o" }, "breakpoints": [{ "line": 1 }] });
+    let body = client.body("setBreakpoints", other);
+    assert_eq!(body["breakpoints"][0]["verified"], false);
+
+    client.body("configurationDone", json!({}));
+    assert_eq!(client.stopped(), "entry");
+    assert_eq!(client.body("threads", json!({}))["threads"][0]["id"], THREAD_ID);
+    assert_eq!(client.stack(), [(2, "main".to_string())]);
+
+    client.body("continue", json!({ "threadId": THREAD_ID }));
+    assert_eq!(client.stopped(), "breakpoint");
+    assert_eq!(client.stack(), [(7, "sub".to_string()), (3, "main".to_string())]);
+    let scopes = client.body("scopes", json!({ "frameId": 0 }));
+    assert_eq!(scopes["scopes"][1]["name"], "Stack");
+    let registers = client.body("variables", json!({ "variablesReference": REGISTERS }));
+    assert_eq!(registers["variables"][0], json!({ "name": "V0", "value": "0x05", "variablesReference": 0 }));
+    assert_eq!(registers["variables"][17]["value"], "0x0208");
+    let stack = client.body("variables", json!({ "variablesReference": STACK }));
+    assert_eq!(stack["variables"][0]["value"], "0x0202");
+
+    client.body("next", json!({ "threadId": THREAD_ID }));
+    assert_eq!(client.stopped(), "step");
+    assert_eq!(client.stack()[0].0, 8);
+    client.body("stepOut", json!({ "threadId": THREAD_ID }));
+    assert_eq!(client.stopped(), "step");
+    assert_eq!(client.stack(), [(4, "main".to_string())]);
+    client.body("stepIn", json!({ "threadId": THREAD_ID }));
+    assert_eq!(client.stopped(), "step");
+
+    let breakpoints = json!({ "breakpoints": [{ "instructionReference": "0x0206" }] });
+    let body = client.body("setInstructionBreakpoints", breakpoints);
+    assert_eq!(body["breakpoints"][0]["verified"], true);
+    client.body("setInstructionBreakpoints", json!({ "breakpoints": [] }));
+    client.body("continue", json!({ "threadId": THREAD_ID }));
+    client.body("pause", json!({ "threadId": THREAD_ID }));
+    assert_eq!(client.stopped(), "pause");
+    assert_eq!(client.stack(), [(5, "main".to_string())]);
+
+    let body = client.body("disassemble", json!({ "memoryReference": "0x0200", "instructionCount": 3 }));
+    let instructions = body["instructions"].as_array().unwrap();
+    assert_eq!(instructions[0]["instruction"], "LD V0, 0x05");
+    assert_eq!(instructions[0]["symbol"], "main");
+    assert_eq!(instructions[1]["instruction"], "CALL 0x208");
+    assert_eq!(instructions[2]["line"], 4);
+
+    client.body("disconnect", json!({}));
+    server.join().unwrap();
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_rom() {
+    let (mut client, server) = Client::connect();
+    client.body("launch", json!({ "program": "roms/test-suite/2-ibm-logo.ch8", "quirks": "vip" }));
+    let breakpoints = json!({ "breakpoints": [{ "instructionReference": "0x20e" }] });
+    let body = client.body("setInstructionBreakpoints", breakpoints);
+    assert_eq!(body["breakpoints"][0]["instructionReference"], "0x020e");
+    client.body("configurationDone", json!({}));
+    assert_eq!(client.event()["event"], "initialized");
+    assert_eq!(client.stopped(), "breakpoint");
+    assert_eq!(client.stack(), [(0, "0x020e".to_string())]);
+    // Dropping the input ends the session
+    drop(client);
+    server.join().unwrap();
+  }
+
+  #[test]
+  fn test_variant() {
+    let path = std::env::temp_dir().join(format!("chip8rs-dap-variant-{}.8o", std::process::id()));
+    fs::write(&path, ": main\n  hires\n  plane 2\n  loop again\n").unwrap();
+    let step = |variant: Value| {
+      let (mut client, server) = Client::connect();
+      client.body("launch", json!({ "program": path, "stopOnEntry": true, "variant": variant }));
+      client.body("configurationDone", json!({}));
+      assert_eq!(client.event()["event"], "initialized");
+      assert_eq!(client.stopped(), "entry");
+      client.body("stepIn", json!({ "threadId": THREAD_ID }));
+      let reason = client.stopped();
+      drop(client);
+      server.join().unwrap();
+      reason
+    };
+    // Without a variant the source runs as plain CHIP-8, which has no hires
+    assert_eq!(step(Value::Null), "exception");
+    assert_eq!(step(json!("xo-chip")), "step");
+
+    let (mut client, server) = Client::connect();
+    let response = client.request("launch", json!({ "program": path, "variant": "vip" }));
+    assert_eq!(response["success"], false);
+    drop(client);
+    server.join().unwrap();
+    fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,394 @@
+use crate::{cli::DebugArgs, error::Chip8Error, headless::write_registers};
+use chip8_core::{Access, Chip8, Chip8Key, Condition, Debugger, Disassembler, Stop, Watchpoint};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+// How long continue runs without hitting anything before giving up, and the
+// most a step can take, e.g. over a subroutine waiting for a key
+const DEFAULT_FRAMES: u64 = 600;
+const DISASM_LINES: usize = 10;
+const MEM_BYTES: usize = 16;
+
+const HELP: &str = "\
+step [N]                  run N instructions (s)
+next                      step, running over a CALL (n)
+finish                    run until the current subroutine returns
+continue [FRAMES]         run until something stops the machine, for at most FRAMES frames (c)
+break [ADDR [if COND]]    set a breakpoint, optionally only when COND holds, or list them (b)
+delete ADDR               remove a breakpoint
+watch ADDR [LEN] [r|w|rw] stop after an instruction writes (or reads) the memory
+unwatch ADDR              remove a watchpoint
+regs                      show the registers (r)
+stack                     show the call stack
+mem ADDR [LEN]            show memory (x)
+disasm [ADDR [COUNT]]     list instructions, from the PC by default (d)
+set REG VALUE             set V0 to VF, I, PC, DT, ST or a byte of memory as [ADDR]
+press KEY, release KEY    hold or let go of a keypad key, 0 to F
+frame                     show the display
+quit                      leave (q)
+Addresses can be I or PC. Conditions compare two values, e.g. V3 == 0x10 or [I] != 0.
+An empty line repeats the last command.";
+
+// Debugs a ROM from commands on stdin, one per line. The prompt is left out
+// unless stdin is a terminal, so scripts can pipe commands in and read back
+// plain output.
+pub fn run(args: &DebugArgs) -> Result<(), Chip8Error> {
+  let (chip8, _) = args.load()?;
+  let stdin = io::stdin();
+  let prompt = stdin.is_terminal();
+  Repl::new(chip8).run(stdin.lock(), &mut io::stdout().lock(), prompt)
+}
+
+struct Repl {
+  chip8: Chip8,
+  debugger: Debugger,
+}
+
+impl Repl {
+  fn new(chip8: Chip8) -> Self {
+    let mut debugger = Debugger::new();
+    debugger.pause();
+    Self { chip8, debugger }
+  }
+
+  fn run(mut self, input: impl BufRead, out: &mut impl Write, prompt: bool) -> Result<(), Chip8Error> {
+    self.write_disasm(out, self.chip8.pc(), 1)?;
+    let mut lines = input.lines();
+    let mut last = String::new();
+    loop {
+      if prompt {
+        write!(out, "(chip8rs) ")?;
+        out.flush()?;
+      }
+      let Some(line) = lines.next().transpose()? else {
+        return Ok(());
+      };
+      let line = match line.trim() {
+        "" => last.clone(),
+        line => line.to_string(),
+      };
+      match self.command(&line, out) {
+        Ok(true) => return Ok(()),
+        Ok(false) => {}
+        // Losing stdout ends the session, anything else is the command's fault
+        Err(Chip8Error::Io(err)) => return Err(err.into()),
+        Err(err) => writeln!(out, "{err}")?,
+      }
+      last = line;
+    }
+  }
+
+  // Returns true to quit
+  fn command(&mut self, line: &str, out: &mut impl Write) -> Result<bool, Chip8Error> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+      return Ok(false);
+    };
+    let args: Vec<&str> = words.collect();
+    match (command, args.as_slice()) {
+      ("quit" | "q" | "exit", []) => return Ok(true),
+      ("help" | "h" | "?", []) => writeln!(out, "{HELP}")?,
+      ("step" | "s", args) => {
+        let count = match args {
+          [] => 1,
+          [count] => parse_number(count)?,
+          _ => return Err(usage(line)),
+        };
+        if count == 0 {
+          return Err(Chip8Error::Usage("Can't step 0 instructions".to_string()));
+        }
+        for _ in 1..count {
+          self.debugger.step();
+          match self.run_frames(DEFAULT_FRAMES) {
+            Some(Stop::Step) => {}
+            stop => return Ok(self.report(stop, out).map(|_| false)?),
+          }
+        }
+        self.debugger.step();
+        self.resume(DEFAULT_FRAMES, out)?;
+      }
+      ("next" | "n", []) => {
+        self.debugger.step_over(&self.chip8);
+        self.resume(DEFAULT_FRAMES, out)?;
+      }
+      ("finish", []) => {
+        self.debugger.step_out(&self.chip8);
+        self.resume(DEFAULT_FRAMES, out)?;
+      }
+      ("continue" | "c", args) => {
+        let frames = match args {
+          [] => DEFAULT_FRAMES,
+          [frames] => parse_number(frames)?,
+          _ => return Err(usage(line)),
+        };
+        self.debugger.resume();
+        self.resume(frames, out)?;
+      }
+      ("break" | "b", []) => {
+        for breakpoint in self.debugger.breakpoints() {
+          match &breakpoint.condition {
+            Some(condition) => writeln!(out, "{:#06x} if {condition}", breakpoint.address)?,
+            None => writeln!(out, "{:#06x}", breakpoint.address)?,
+          }
+        }
+      }
+      ("break" | "b", [address, rest @ ..]) => {
+        let address = self.address(address)?;
+        let condition = match rest {
+          [] => None,
+          ["if", ..] => {
+            let condition = line.split_once(" if ").map_or("", |(_, condition)| condition);
+            Some(condition.parse::<Condition>().map_err(|err| Chip8Error::Usage(err.to_string()))?)
+          }
+          _ => return Err(usage(line)),
+        };
+        self.debugger.add_breakpoint(address, condition);
+      }
+      ("delete", [address]) => {
+        let address = self.address(address)?;
+        if !self.debugger.remove_breakpoint(address) {
+          writeln!(out, "No breakpoint at {address:#06x}")?;
+        }
+      }
+      ("watch", [address, rest @ ..]) => {
+        let address = self.address(address)? as usize;
+        let mut watchpoint = Watchpoint { address, len: 1, access: Access::Write };
+        for arg in rest {
+          match *arg {
+            "r" => watchpoint.access = Access::Read,
+            "w" => watchpoint.access = Access::Write,
+            "rw" => watchpoint.access = Access::ReadWrite,
+            len => watchpoint.len = parse_number(len)?,
+          }
+        }
+        self.chip8.memory_mut().add_watchpoint(watchpoint)?;
+      }
+      ("watch", []) => {
+        for watchpoint in self.chip8.memory().watchpoints() {
+          let Watchpoint { address, len, access } = watchpoint;
+          writeln!(out, "{address:#06x} {len} {access:?}")?;
+        }
+      }
+      ("unwatch", [address]) => {
+        let address = self.address(address)? as usize;
+        if !self.chip8.memory_mut().remove_watchpoint(address) {
+          writeln!(out, "No watchpoint at {address:#06x}")?;
+        }
+      }
+      ("regs" | "r", []) => write_registers(out, &self.chip8)?,
+      ("stack", []) => {
+        // Innermost call first
+        for (depth, address) in self.chip8.stack().iter().rev().enumerate() {
+          writeln!(out, "#{depth} {address:#06x}")?;
+        }
+      }
+      ("mem" | "x", [address, rest @ ..]) => {
+        let address = self.address(address)? as usize;
+        let len = match rest {
+          [] => MEM_BYTES,
+          [len] => parse_number(len)?,
+          _ => return Err(usage(line)),
+        };
+        let bytes = self.chip8.memory().peek(address, len)?;
+        for (row, chunk) in bytes.chunks(MEM_BYTES).enumerate() {
+          let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02X}")).collect();
+          writeln!(out, "{:04X}  {}", address + row * MEM_BYTES, hex.join(" "))?;
+        }
+      }
+      ("disasm" | "d", args) => {
+        let (address, count) = match args {
+          [] => (self.chip8.pc(), DISASM_LINES),
+          [address] => (self.address(address)?, DISASM_LINES),
+          [address, count] => (self.address(address)?, parse_number(count)?),
+          _ => return Err(usage(line)),
+        };
+        self.write_disasm(out, address, count)?;
+      }
+      ("set", [target, value]) => self.set(target, parse_number(value)?)?,
+      ("press" | "release", [key]) => {
+        let key = u8::from_str_radix(key, 16)
+          .ok()
+          .and_then(|key| Chip8Key::from_index(key as usize))
+          .ok_or_else(|| Chip8Error::Usage(format!("Invalid key {key}, expected 0 to F")))?;
+        self.chip8.set_key(key, command == "press");
+      }
+      ("frame", []) => write!(out, "{}", self.chip8.frame_buffer())?,
+      _ => return Err(Chip8Error::Usage(format!("Unknown command {line}, try help"))),
+    }
+    Ok(false)
+  }
+
+  // Runs frames until the debugger stops the machine, or pauses it once
+  // they're used up
+  fn run_frames(&mut self, frames: u64) -> Option<Stop> {
+    let stop = (0..frames).find_map(|_| self.debugger.run_frame(&mut self.chip8));
+    if stop.is_none() {
+      self.debugger.pause();
+    }
+    stop
+  }
+
+  fn resume(&mut self, frames: u64, out: &mut impl Write) -> io::Result<()> {
+    let stop = self.run_frames(frames);
+    if stop.is_none() {
+      writeln!(out, "Paused after {frames} frames")?;
+    }
+    self.report(stop, out)
+  }
+
+  // Says why the machine stopped and where it is
+  fn report(&self, stop: Option<Stop>, out: &mut impl Write) -> io::Result<()> {
+    match stop {
+      None | Some(Stop::Step) => {}
+      Some(stop) => writeln!(out, "{stop}")?,
+    }
+    self.write_disasm(out, self.chip8.pc(), 1)
+  }
+
+  fn write_disasm(&self, out: &mut impl Write, address: u16, count: usize) -> io::Result<()> {
+    let memory = self.chip8.memory();
+    let start = (address as usize).min(memory.size());
+    // Instructions are at most four bytes long
+    let len = (count * 4).min(memory.size() - start);
+    let bytes = memory.peek(start, len).unwrap_or_default();
+    for line in Disassembler::new(bytes, self.chip8.variant()).with_origin(address).take(count) {
+      let marker = if line.address == self.chip8.pc() { "=>" } else { "  " };
+      writeln!(out, "{marker} {line}")?;
+    }
+    Ok(())
+  }
+
+  // A number, or I or PC for their current values
+  fn address(&self, text: &str) -> Result<u16, Chip8Error> {
+    match text.to_ascii_lowercase().as_str() {
+      "i" => Ok(self.chip8.i()),
+      "pc" => Ok(self.chip8.pc()),
+      _ => parse_number(text),
+    }
+  }
+
+  fn set(&mut self, target: &str, value: u16) -> Result<(), Chip8Error> {
+    let byte = || u8::try_from(value).map_err(|_| Chip8Error::Usage(format!("{value} doesn't fit in a byte")));
+    let lower = target.to_ascii_lowercase();
+    if let Some(address) = lower.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+      let address = self.address(address)?;
+      let memory = self.chip8.memory_mut();
+      memory.write_byte(address as usize, byte()?)?;
+      // Changes made from the prompt aren't the program's doing
+      memory.take_watch_hit();
+      return Ok(());
+    }
+    let registers = self.chip8.registers_mut();
+    match lower.as_str() {
+      "i" => registers.i = value,
+      "pc" => registers.pc = value,
+      "dt" => registers.delay_timer = byte()?,
+      "st" => registers.sound_timer = byte()?,
+      _ => {
+        let index = lower
+          .strip_prefix('v')
+          .filter(|digit| digit.len() == 1)
+          .and_then(|digit| usize::from_str_radix(digit, 16).ok())
+          .ok_or_else(|| Chip8Error::Usage(format!("Unknown register {target}")))?;
+        registers.v[index] = byte()?;
+      }
+    }
+    Ok(())
+  }
+}
+
+fn usage(line: &str) -> Chip8Error {
+  Chip8Error::Usage(format!("Invalid arguments in {line}, try help"))
+}
+
+// Decimal, or hex with a 0x prefix
+fn parse_number<T: TryFrom<u64>>(text: &str) -> Result<T, Chip8Error> {
+  let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+    Some(hex) => u64::from_str_radix(hex, 16),
+    None => text.parse(),
+  };
+  value
+    .ok()
+    .and_then(|value| T::try_from(value).ok())
+    .ok_or_else(|| Chip8Error::Usage(format!("Invalid number {text}")))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chip8_core::Quirks;
+
+  // Runs a script of commands against the IBM logo and returns the output
+  fn session(script: &str) -> String {
+    session_rom(include_bytes!("../roms/test-suite/2-ibm-logo.ch8"), script)
+  }
+
+  fn session_rom(rom: &[u8], script: &str) -> String {
+    let mut chip8 = Chip8::new(Quirks::schip_modern());
+    chip8.load_rom(rom).unwrap();
+    let mut out = Vec::new();
+    Repl::new(chip8).run(script.as_bytes(), &mut out, false).unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[test]
+  fn test_stepping() {
+    let out = session("step\n\nregs\nnext\nstep 3\ndisasm pc 2\nquit\nstep");
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "=> 0200  00E0      CLS");
+    assert_eq!(lines[1], "=> 0202  A22A      LD I, 0x22A");
+    // The empty line repeats the step
+    assert_eq!(lines[2], "=> 0204  600C      LD V0, 0x0C");
+    assert_eq!(lines[3], "PC 0x0204  I 0x022a  DT 0x00  ST 0x00");
+    assert_eq!(lines[6], "=> 0206  6108      LD V1, 0x08");
+    assert_eq!(lines[7], "=> 020C  A239      LD I, 0x239");
+    assert_eq!(&lines[8..], ["=> 020C  A239      LD I, 0x239", "   020E  D01F      DRW V0, V1, 15"]);
+
+    let out = session("step 0\nregs");
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[1], "Can't step 0 instructions");
+    assert_eq!(lines[2], "PC 0x0200  I 0x0000  DT 0x00  ST 0x00");
+  }
+
+  #[test]
+  fn test_breakpoints() {
+    let out = session("break 0x228\nbreak 0x20E if V0 == 0x15\nbreak\ncontinue\ncontinue\ndelete 0x228\ndelete 0x228");
+    assert_eq!(
+      out.lines().collect::<Vec<_>>(),
+      [
+        "=> 0200  00E0      CLS",
+        "0x0228",
+        "0x020e if V0 == 0x15",
+        "Breakpoint at 0x020e",
+        "=> 020E  D01F      DRW V0, V1, 15",
+        "Breakpoint at 0x0228",
+        "=> 0228  1228      JP 0x228",
+        "No breakpoint at 0x0228",
+      ]
+    );
+    let out = session("continue 5\nframe");
+    assert!(out.contains("Paused after 5 frames\n=> 0228"));
+    assert!(out.lines().skip(3).all(|line| line.len() == 64));
+    assert!(out.contains('#'));
+  }
+
+  #[test]
+  fn test_memory() {
+    // LD I, 0x300; LD V0, 0xAB; LD [I], V0; JP 0x206
+    let rom = [0xA3, 0x00, 0x60, 0xAB, 0xF0, 0x55, 0x12, 0x06];
+    let out = session_rom(&rom, "watch 0x300 2 rw\nset [0x300] 7\nmem 0x300 2\ncontinue\nmem 0x300 2");
+    let lines: Vec<&str> = out.lines().collect();
+    // Writes from the prompt don't trip the watchpoint, the program's do
+    assert_eq!(lines[1], "0300  07 00");
+    assert_eq!(lines[2], "Watched address 0x0300 written by the instruction at 0x0204");
+    assert_eq!(lines[3], "=> 0206  1206      JP 0x206");
+    assert_eq!(lines[4], "0300  AB 00");
+
+    let out = session("set V3 7\nset DT 300\nset VG 1\nbogus\npress 5\npress G\nstack\nx i 2");
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[1], "300 doesn't fit in a byte");
+    assert_eq!(lines[2], "Unknown register VG");
+    assert_eq!(lines[3], "Unknown command bogus, try help");
+    assert_eq!(lines[4], "Invalid key G, expected 0 to F");
+    assert_eq!(lines[5], "0000  00 00");
+  }
+}
@@ -0,0 +1,21 @@
+use crate::{cli::DisasmArgs, error::Chip8Error, read_rom, rom_sha1, rom_variant, romdb};
+use chip8_core::{Disassembler, Syntax};
+use std::io::{self, BufWriter, Write};
+
+// Prints a listing of a ROM. Unless told otherwise the instruction set is the
+// one the ROM database or the file extension says the ROM was made for.
+pub fn run(args: &DisasmArgs) -> Result<(), Chip8Error> {
+  let rom = read_rom(&args.rom)?;
+  let variant = args.variant.unwrap_or_else(|| match romdb::lookup(&rom_sha1(&rom)) {
+    Some(info) => info.variant,
+    None => rom_variant(&args.rom),
+  });
+  let syntax = if args.octo { Syntax::Octo } else { Syntax::Mnemonic };
+
+  let mut out = BufWriter::new(io::stdout().lock());
+  for line in Disassembler::new(&rom, variant).with_syntax(syntax) {
+    writeln!(out, "{line}")?;
+  }
+  out.flush()?;
+  Ok(())
+}
@@ -1,4 +1,5 @@
-use crate::interpreter::error::InterpreterError;
+use crate::config::ConfigError;
+use chip8_core::{AssemblerError, InterpreterError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,4 +8,14 @@ pub enum Chip8Error {
   PixelsError(#[from] pixels::Error),
   #[error("Interpreter Error: {0}")]
   InterpreterError(#[from] InterpreterError),
+  #[error("IO Error: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("PNG Error: {0}")]
+  Png(#[from] png::EncodingError),
+  #[error("Assembler Error: {0}")]
+  Assembler(#[from] AssemblerError),
+  #[error("Config Error: {0}")]
+  Config(#[from] ConfigError),
+  #[error("{0}")]
+  Usage(String),
 }
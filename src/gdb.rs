@@ -0,0 +1,523 @@
+use crate::{cli::GdbArgs, error::Chip8Error};
+use chip8_core::{Access, Chip8, Debugger, Stop, Watchpoint};
+use log::{error, info};
+use std::{
+  fmt::Write as _,
+  io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+  net::{Ipv4Addr, TcpListener, TcpStream},
+  time::{Duration, Instant},
+};
+
+// How long a continue waits on the socket for an interrupt before running the
+// frames that came due, about one frame
+const POLL: Duration = Duration::from_millis(16);
+const PACKET_SIZE: usize = 0x1000;
+
+// GDB has no CHIP-8 architecture, so the registers are described to it. They
+// go over the wire in this order, with I and PC little-endian.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8rs.cpu">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+const REGISTERS: usize = 21;
+
+// Serves a ROM to GDB over TCP on localhost, one connection at a time, until a
+// client kills it
+pub fn run(args: &GdbArgs) -> Result<(), Chip8Error> {
+  let (chip8, _) = args.machine.load()?;
+  let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, args.port))?;
+  info!("Waiting for GDB on {}", listener.local_addr()?);
+  let mut stub = GdbStub::new(chip8);
+  for stream in listener.incoming() {
+    let stream = stream?;
+    info!("GDB connected from {}", stream.peer_addr()?);
+    if stub.serve(stream)? == Session::Killed {
+      break;
+    }
+  }
+  Ok(())
+}
+
+// A GDB remote serial protocol server for one machine. The machine starts out
+// halted and only runs on `s` and `c`.
+struct GdbStub {
+  chip8: Chip8,
+  debugger: Debugger,
+  no_ack: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Session {
+  Detached,
+  Killed,
+}
+
+struct Connection {
+  reader: BufReader<TcpStream>,
+  writer: TcpStream,
+}
+
+impl GdbStub {
+  fn new(chip8: Chip8) -> Self {
+    let mut debugger = Debugger::new();
+    debugger.pause();
+    Self { chip8, debugger, no_ack: false }
+  }
+
+  fn serve(&mut self, stream: TcpStream) -> io::Result<Session> {
+    self.no_ack = false;
+    // Packets are small and each waits on the last, so don't let them queue
+    stream.set_nodelay(true)?;
+    let mut connection = Connection { reader: BufReader::new(stream.try_clone()?), writer: stream };
+    while let Some(packet) = self.read_packet(&mut connection)? {
+      let reply = match packet.as_str() {
+        "D" | "D;1" => {
+          self.send(&mut connection, "OK")?;
+          return Ok(Session::Detached);
+        }
+        "k" | "vKill;1" => return Ok(Session::Killed),
+        _ if packet.starts_with(['c', 's']) => self.resume(&packet, &mut connection)?,
+        _ => self.handle(&packet).unwrap_or_else(|| "E01".to_string()),
+      };
+      self.send(&mut connection, &reply)?;
+    }
+    // The client hung up without detaching
+    Ok(Session::Detached)
+  }
+
+  // Replies to a packet that doesn't run the machine. None is an error reply,
+  // and an empty reply tells GDB the packet isn't supported.
+  fn handle(&mut self, packet: &str) -> Option<String> {
+    // Query names run up to their arguments, everything else is one letter
+    let split = match packet.starts_with(['q', 'Q', 'v']) {
+      true => packet.find([':', ',', ';']).unwrap_or(packet.len()),
+      false => 1,
+    };
+    let (command, args) = packet.split_at_checked(split)?;
+    let reply = match (command, args) {
+      ("?", "") => "S05".to_string(),
+      ("qSupported", _) => format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+"),
+      ("QStartNoAckMode", "") => {
+        self.no_ack = true;
+        "OK".to_string()
+      }
+      ("qXfer", _) => {
+        let (offset, len) = args.strip_prefix(":features:read:target.xml:")?.split_once(',')?;
+        let offset = usize::from_str_radix(offset, 16).ok()?.min(TARGET_XML.len());
+        let len = usize::from_str_radix(len, 16).ok()?;
+        let chunk = &TARGET_XML[offset..(offset + len).min(TARGET_XML.len())];
+        // m for more to come, l for the last part
+        let more = if offset + chunk.len() < TARGET_XML.len() { 'm' } else { 'l' };
+        format!("{more}{chunk}")
+      }
+      ("qAttached", _) => "1".to_string(),
+      // One thread, always current
+      ("H", _) => "OK".to_string(),
+      ("qC", "") => "QC1".to_string(),
+      ("qfThreadInfo", "") => "m1".to_string(),
+      ("qsThreadInfo", "") => "l".to_string(),
+      ("g", "") => {
+        let mut hex = String::new();
+        for index in 0..REGISTERS {
+          hex.push_str(&self.read_register(index)?);
+        }
+        hex
+      }
+      ("G", hex) => {
+        let mut bytes = decode_hex(hex)?.into_iter();
+        for index in 0..REGISTERS {
+          let width = register_width(index);
+          let value: Vec<u8> = bytes.by_ref().take(width).collect();
+          if value.len() != width {
+            return None;
+          }
+          self.write_register(index, &value)?;
+        }
+        "OK".to_string()
+      }
+      ("p", index) => self.read_register(usize::from_str_radix(index, 16).ok()?)?,
+      ("P", args) => {
+        let (index, value) = args.split_once('=')?;
+        self.write_register(usize::from_str_radix(index, 16).ok()?, &decode_hex(value)?)?;
+        "OK".to_string()
+      }
+      ("m", args) => {
+        let (address, len) = parse_range(args)?;
+        encode_hex(self.chip8.memory().peek(address, len).ok()?)
+      }
+      ("M", args) => {
+        let (range, data) = args.split_once(':')?;
+        let (address, len) = parse_range(range)?;
+        let data = decode_hex(data).filter(|data| data.len() == len)?;
+        let memory = self.chip8.memory_mut();
+        memory.write(address, &data).ok()?;
+        // Writes from GDB aren't the program's doing
+        memory.take_watch_hit();
+        "OK".to_string()
+      }
+      ("Z" | "z", args) => {
+        let mut fields = args.split(',');
+        let kind = fields.next()?;
+        let address = usize::from_str_radix(fields.next()?, 16).ok()?;
+        let len = usize::from_str_radix(fields.next()?, 16).ok()?;
+        let insert = command == "Z";
+        let access = match kind {
+          // Software and hardware breakpoints are the same thing here
+          "0" | "1" => {
+            let address = u16::try_from(address).ok()?;
+            match insert {
+              true => self.debugger.add_breakpoint(address, None),
+              false => _ = self.debugger.remove_breakpoint(address),
+            }
+            return Some("OK".to_string());
+          }
+          "2" => Access::Write,
+          "3" => Access::Read,
+          "4" => Access::ReadWrite,
+          _ => return Some(String::new()),
+        };
+        let memory = self.chip8.memory_mut();
+        match insert {
+          true => memory.add_watchpoint(Watchpoint { address, len, access }).ok()?,
+          false => _ = memory.remove_watchpoint(address),
+        }
+        "OK".to_string()
+      }
+      _ => String::new(),
+    };
+    Some(reply)
+  }
+
+  // Steps or continues, optionally from a new address, and replies once the
+  // machine stops. The machine runs in real time and a ^C from GDB interrupts
+  // it.
+  fn resume(&mut self, packet: &str, connection: &mut Connection) -> io::Result<String> {
+    let (command, address) = packet.split_at(1);
+    if !address.is_empty() {
+      let Ok(address) = u16::from_str_radix(address, 16) else {
+        return Ok("E01".to_string());
+      };
+      self.chip8.registers_mut().pc = address;
+    }
+    match command {
+      "s" => self.debugger.step(),
+      _ => self.debugger.resume(),
+    }
+    // The first frame runs straight away, which is as long as a step takes
+    // unless it waits on a key or the display
+    if let Some(stop) = self.debugger.run_frame(&mut self.chip8) {
+      return Ok(self.stop_reply(stop));
+    }
+
+    connection.reader.get_ref().set_read_timeout(Some(POLL))?;
+    let mut last = Instant::now();
+    let reply = loop {
+      match connection.reader.fill_buf() {
+        Ok([0x03, ..]) => {
+          connection.reader.consume(1);
+          self.debugger.pause();
+          break "S02".to_string();
+        }
+        Ok([]) => break "S02".to_string(),
+        // Anything else is an ack, or a packet GDB shouldn't send while
+        // the machine runs
+        Ok(bytes) => {
+          let len = bytes.len();
+          connection.reader.consume(len);
+        }
+        Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+        Err(err) => return Err(err),
+      }
+      let now = Instant::now();
+      if let Some(stop) = self.debugger.update(&mut self.chip8, &(now - last)) {
+        break self.stop_reply(stop);
+      }
+      last = now;
+    };
+    connection.reader.get_ref().set_read_timeout(None)?;
+    Ok(reply)
+  }
+
+  fn stop_reply(&self, stop: Stop) -> String {
+    match stop {
+      Stop::Step | Stop::Breakpoint(_) => "S05".to_string(),
+      Stop::Watchpoint { hit, .. } => {
+        // GDB wants to hear which kind of watchpoint it set was hit
+        let access = self
+          .chip8
+          .memory()
+          .watchpoints()
+          .find(|watchpoint| (watchpoint.address..watchpoint.address + watchpoint.len).contains(&hit.address))
+          .map_or(hit.access, |watchpoint| watchpoint.access);
+        let kind = match access {
+          Access::Write => "watch",
+          Access::Read => "rwatch",
+          Access::ReadWrite => "awatch",
+        };
+        format!("T05{kind}:{:x};", hit.address)
+      }
+      Stop::Exited => "W00".to_string(),
+      // Reported as an illegal instruction, the PC is left on it
+      Stop::Fault(err) => {
+        error!("{err}");
+        "S04".to_string()
+      }
+    }
+  }
+
+  fn read_register(&self, index: usize) -> Option<String> {
+    let chip8 = &self.chip8;
+    let bytes = match index {
+      0..=15 => vec![chip8.v()[index]],
+      16 => chip8.i().to_le_bytes().to_vec(),
+      17 => chip8.pc().to_le_bytes().to_vec(),
+      18 => vec![chip8.stack().len() as u8],
+      19 => vec![chip8.delay_timer()],
+      20 => vec![chip8.sound_timer()],
+      _ => return None,
+    };
+    Some(encode_hex(&bytes))
+  }
+
+  fn write_register(&mut self, index: usize, bytes: &[u8]) -> Option<()> {
+    if bytes.len() != register_width(index) {
+      return None;
+    }
+    let registers = self.chip8.registers_mut();
+    match index {
+      0..=15 => registers.v[index] = bytes[0],
+      16 => registers.i = u16::from_le_bytes([bytes[0], bytes[1]]),
+      17 => registers.pc = u16::from_le_bytes([bytes[0], bytes[1]]),
+      18 if (bytes[0] as usize) <= registers.stack.len() => registers.sp = bytes[0] as usize,
+      19 => registers.delay_timer = bytes[0],
+      20 => registers.sound_timer = bytes[0],
+      _ => return None,
+    }
+    Some(())
+  }
+
+  // Reads up to the next packet and acknowledges it, or None once the client
+  // hangs up. Acks from GDB and stray interrupts are skipped.
+  fn read_packet(&self, connection: &mut Connection) -> io::Result<Option<String>> {
+    loop {
+      let mut data = Vec::new();
+      connection.reader.skip_until(b'$')?;
+      if connection.reader.read_until(b'#', &mut data)? == 0 || data.pop() != Some(b'#') {
+        return Ok(None);
+      }
+      let mut checksum = [0; 2];
+      connection.reader.read_exact(&mut checksum)?;
+      let valid = std::str::from_utf8(&checksum)
+        .ok()
+        .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+        .is_some_and(|checksum| checksum == sum(&data));
+      if !self.no_ack {
+        connection.writer.write_all(if valid { b"+" } else { b"-" })?;
+      }
+      if valid {
+        return Ok(Some(String::from_utf8_lossy(&unescape(&data)).into_owned()));
+      }
+    }
+  }
+
+  fn send(&self, connection: &mut Connection, data: &str) -> io::Result<()> {
+    let escaped = escape(data.as_bytes());
+    let mut packet = Vec::with_capacity(escaped.len() + 4);
+    packet.push(b'$');
+    packet.extend_from_slice(&escaped);
+    packet.extend_from_slice(format!("#{:02x}", sum(&escaped)).as_bytes());
+    connection.writer.write_all(&packet)?;
+    connection.writer.flush()
+  }
+}
+
+fn register_width(index: usize) -> usize {
+  match index {
+    16 | 17 => 2,
+    _ => 1,
+  }
+}
+
+fn sum(data: &[u8]) -> u8 {
+  data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+// #, $, } and * are sent as } followed by the byte xor 0x20
+fn escape(data: &[u8]) -> Vec<u8> {
+  let mut escaped = Vec::with_capacity(data.len());
+  for &byte in data {
+    match byte {
+      b'#' | b'$' | b'}' | b'*' => escaped.extend_from_slice(&[b'}', byte ^ 0x20]),
+      _ => escaped.push(byte),
+    }
+  }
+  escaped
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+  let mut bytes = data.iter();
+  let mut unescaped = Vec::with_capacity(data.len());
+  while let Some(&byte) = bytes.next() {
+    match byte {
+      b'}' => unescaped.extend(bytes.next().map(|byte| byte ^ 0x20)),
+      _ => unescaped.push(byte),
+    }
+  }
+  unescaped
+}
+
+// "addr,len" in hex
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+  let (address, len) = args.split_once(',')?;
+  Some((usize::from_str_radix(address, 16).ok()?, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+  bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+    _ = write!(hex, "{byte:02x}");
+    hex
+  })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+  if !hex.len().is_multiple_of(2) {
+    return None;
+  }
+  (0..hex.len())
+    .step_by(2)
+    .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chip8_core::Quirks;
+  use std::thread;
+
+  // A GDB stand-in talking to a stub over loopback
+  struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+  }
+
+  impl Client {
+    fn connect(rom: &[u8]) -> (Self, thread::JoinHandle<Session>) {
+      let mut chip8 = Chip8::new(Quirks::schip_modern());
+      chip8.load_rom(rom).unwrap();
+      let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+      let address = listener.local_addr().unwrap();
+      let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new(chip8).serve(stream).unwrap()
+      });
+      let stream = TcpStream::connect(address).unwrap();
+      stream.set_nodelay(true).unwrap();
+      let client = Self { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream };
+      (client, server)
+    }
+
+    // Sends a packet and returns the reply, checking the acks on the way
+    fn request(&mut self, data: &str) -> String {
+      write!(self.writer, "${data}#{:02x}", sum(data.as_bytes())).unwrap();
+      let mut ack = [0];
+      self.reader.read_exact(&mut ack).unwrap();
+      assert_eq!(ack, *b"+", "{data} wasn't acknowledged");
+      self.reply()
+    }
+
+    fn reply(&mut self) -> String {
+      let mut packet = Vec::new();
+      self.reader.read_until(b'#', &mut packet).unwrap();
+      let mut checksum = [0; 2];
+      self.reader.read_exact(&mut checksum).unwrap();
+      self.writer.write_all(b"+").unwrap();
+      assert_eq!(packet.remove(0), b'$');
+      packet.pop();
+      assert_eq!(format!("{:02x}", sum(&packet)), String::from_utf8_lossy(&checksum));
+      String::from_utf8(unescape(&packet)).unwrap()
+    }
+  }
+
+  #[test]
+  fn test_session() {
+    let (mut client, server) = Client::connect(include_bytes!("../roms/test-suite/2-ibm-logo.ch8"));
+    assert!(client.request("qSupported:multiprocess+;swbreak+").contains("qXfer:features:read+"));
+    assert_eq!(client.request("?"), "S05");
+    assert!(client.request("qXfer:features:read:target.xml:0,40").starts_with("m<?xml"));
+    assert!(client.request("qXfer:features:read:target.xml:0,1000").starts_with('l'));
+    assert_eq!(client.request("vMustReplyEmpty"), "");
+
+    // V0 to VF, I, PC, SP, DT and ST
+    assert_eq!(client.request("g"), format!("{}00000002{}", "00".repeat(16), "00".repeat(3)));
+    assert_eq!(client.request("m200,4"), "00e0a22a");
+    assert_eq!(client.request("m1000,1"), "E01");
+
+    assert_eq!(client.request("Z0,20e,2"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p11"), "0e02");
+    assert_eq!(client.request("p0"), "15");
+    assert_eq!(client.request("p10"), "3902");
+    assert_eq!(client.request("z0,20e,2"), "OK");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "1002");
+
+    assert_eq!(client.request("P3=07"), "OK");
+    assert_eq!(client.request("P10=0003"), "OK");
+    assert_eq!(client.request("p3"), "07");
+    assert_eq!(client.request("M300,2:abcd"), "OK");
+    assert_eq!(client.request("m300,2"), "abcd");
+    assert_eq!(client.request("M300,2:ab"), "E01");
+
+    // The next instruction, LD I, 0x248, reads nothing, so the watchpoint
+    // on the sprite it points at trips on the DRW after it
+    assert_eq!(client.request("Z3,248,f"), "OK");
+    assert_eq!(client.request("c"), "T05rwatch:248;");
+    assert_eq!(client.request("z3,248,f"), "OK");
+
+    // Nothing else stops the logo's final loop, so it takes an interrupt
+    client.writer.write_all(b"$c#63").unwrap();
+    let mut ack = [0];
+    client.reader.read_exact(&mut ack).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    client.writer.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+    assert_eq!(client.request("p11"), "2802");
+
+    client.writer.write_all(b"$k#6b").unwrap();
+    assert_eq!(server.join().unwrap(), Session::Killed);
+  }
+
+  #[test]
+  fn test_escapes() {
+    assert_eq!(escape(b"a#b}"), b"a}\x03b}]");
+    assert_eq!(unescape(&escape(b"$*#}")), b"$*#}");
+    assert_eq!(decode_hex("0aff"), Some(vec![0x0A, 0xFF]));
+    assert_eq!(decode_hex("0"), None);
+    assert_eq!(encode_hex(&[0x0A, 0xFF]), "0aff");
+  }
+}
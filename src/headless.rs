@@ -0,0 +1,234 @@
+use crate::{cli::Cli, error::Chip8Error};
+use chip8_core::{
+  audio::{wav::WavSink, AudioSink, Sound},
+  Chip8,
+  Chip8Key,
+};
+use std::{
+  fs::File,
+  io::{self, BufWriter, Write},
+  path::Path,
+};
+
+const DEFAULT_FRAMES: u64 = 600;
+const WAV_SAMPLE_RATE: u32 = 44100;
+
+// A scripted key change, applied before the given frame runs
+#[derive(Debug, PartialEq)]
+struct KeyEvent {
+  frame: u64,
+  key: Chip8Key,
+  pressed: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+  frames: Option<u64>,
+  cycles: Option<u64>,
+  keys: Vec<KeyEvent>,
+}
+
+// Runs a ROM without opening a window, then prints the registers and
+// optionally dumps the display. Interpreter errors are returned so the
+// process exits non-zero.
+pub fn run(cli: &Cli, path: &Path) -> Result<(), Chip8Error> {
+  let options = Options {
+    frames: cli.frames,
+    cycles: cli.cycles,
+    keys: cli.keys.as_deref().map(parse_keys).transpose()?.unwrap_or_default(),
+  };
+  let (mut chip8, config) = cli.load(path)?;
+
+  let mut frames = 0;
+  let mut cycles = 0;
+  let mut wav = cli.wav.as_ref().map(|_| WavSink::new(WAV_SAMPLE_RATE));
+  let audio = wav.as_mut().map(|wav| wav as &mut dyn AudioSink);
+  let result = run_frames(&mut chip8, &options, audio, &mut frames, &mut cycles);
+  print_registers(&chip8, frames, cycles);
+  if cli.ascii {
+    print!("{}", chip8.frame_buffer());
+  }
+  if let Some(path) = &cli.png {
+    write_png(&chip8, &config.palette, path)?;
+  }
+  if let (Some(path), Some(wav)) = (&cli.wav, &wav) {
+    let mut writer = BufWriter::new(File::create(path)?);
+    wav.write_wav(&mut writer)?;
+    writer.flush()?;
+  }
+  result
+}
+
+// Parses "FRAME:KEY+" (press) and "FRAME:KEY-" (release) entries separated by
+// commas, where KEY is the hex keypad digit
+fn parse_keys(script: &str) -> Result<Vec<KeyEvent>, Chip8Error> {
+  let mut events = script
+    .split(',')
+    .filter(|entry| !entry.trim().is_empty())
+    .map(|entry| {
+      let invalid = || Chip8Error::Usage(format!("Invalid key event {entry}"));
+      let (frame, key) = entry.trim().split_once(':').ok_or_else(invalid)?;
+      let (key, pressed) = match key.strip_suffix('+') {
+        Some(key) => (key, true),
+        None => (key.strip_suffix('-').ok_or_else(invalid)?, false),
+      };
+      let key = usize::from_str_radix(key, 16)
+        .ok()
+        .and_then(Chip8Key::from_index)
+        .ok_or_else(invalid)?;
+      Ok(KeyEvent {
+        frame: frame.parse().map_err(|_| invalid())?,
+        key,
+        pressed,
+      })
+    })
+    .collect::<Result<Vec<_>, Chip8Error>>()?;
+  events.sort_by_key(|event| event.frame);
+  Ok(events)
+}
+
+// Runs until the frame or cycle budget is spent or the ROM exits. Cycle
+// budgets are rounded up to a whole frame. Each frame's sound goes to audio.
+fn run_frames(
+  chip8: &mut Chip8,
+  options: &Options,
+  mut audio: Option<&mut dyn AudioSink>,
+  frames: &mut u64,
+  cycles: &mut u64,
+) -> Result<(), Chip8Error> {
+  let mut keys = options.keys.iter().peekable();
+  let done = |frames: u64, cycles: u64| match options.cycles {
+    Some(limit) => cycles >= limit,
+    None => frames >= options.frames.unwrap_or(DEFAULT_FRAMES),
+  };
+  while !chip8.exited() && !done(*frames, *cycles) {
+    while let Some(event) = keys.next_if(|event| event.frame <= *frames) {
+      chip8.set_key(event.key, event.pressed);
+    }
+    *cycles += chip8.run_frame()? as u64;
+    *frames += 1;
+    if let Some(audio) = audio.as_mut() {
+      audio.play(&Sound::from_chip8(chip8));
+    }
+  }
+  Ok(())
+}
+
+fn print_registers(chip8: &Chip8, frames: u64, cycles: u64) {
+  println!("frames {frames}  cycles {cycles}");
+  // Nothing useful can be done if stdout is gone
+  let _ = write_registers(&mut io::stdout().lock(), chip8);
+}
+
+// Also the debugger's regs command
+pub fn write_registers(out: &mut impl Write, chip8: &Chip8) -> io::Result<()> {
+  writeln!(
+    out,
+    "PC {:#06x}  I {:#06x}  DT {:#04x}  ST {:#04x}",
+    chip8.pc(),
+    chip8.i(),
+    chip8.delay_timer(),
+    chip8.sound_timer(),
+  )?;
+  let v: Vec<String> = chip8
+    .v()
+    .iter()
+    .enumerate()
+    .map(|(index, value)| format!("V{index:X} {value:#04x}"))
+    .collect();
+  writeln!(out, "{}", v.join("  "))?;
+  let stack: Vec<String> = chip8.stack().iter().map(|addr| format!("{addr:#06x}")).collect();
+  writeln!(out, "stack [{}]", stack.join(", "))
+}
+
+fn write_png(chip8: &Chip8, palette: &[[u8; 4]; 4], path: &Path) -> Result<(), Chip8Error> {
+  let (width, height) = chip8.resolution();
+  let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width as u32, height as u32);
+  encoder.set_color(png::ColorType::Rgba);
+  encoder.set_depth(png::BitDepth::Eight);
+  let data: Vec<u8> = chip8
+    .frame()
+    .iter()
+    .flat_map(|&pixel| palette[pixel as usize])
+    .collect();
+  encoder.write_header()?.write_image_data(&data)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_keys() {
+    let keys = parse_keys("20:5-,10:5+,10:f+").unwrap();
+    assert_eq!(
+      keys,
+      vec![
+        KeyEvent { frame: 10, key: Chip8Key::W, pressed: true },
+        KeyEvent { frame: 10, key: Chip8Key::V, pressed: true },
+        KeyEvent { frame: 20, key: Chip8Key::W, pressed: false },
+      ]
+    );
+    assert!(parse_keys("10:5").is_err());
+    assert!(parse_keys("10:g+").is_err());
+    assert!(parse_keys("x:5+").is_err());
+  }
+
+  #[test]
+  fn test_run_frames() {
+    let mut chip8 = Chip8::default();
+    chip8.load_rom(include_bytes!("../roms/test-suite/2-ibm-logo.ch8")).unwrap();
+    let options = Options {
+      frames: Some(30),
+      ..Options::default()
+    };
+    let (mut frames, mut cycles) = (0, 0);
+    run_frames(&mut chip8, &options, None, &mut frames, &mut cycles).unwrap();
+    assert_eq!(frames, 30);
+    let ascii = chip8.frame_buffer().to_string();
+    assert_eq!(ascii.lines().count(), 32);
+    assert!(ascii.lines().all(|line| line.len() == 64));
+    assert!(ascii.contains('#'));
+
+    let mut chip8 = Chip8::default();
+    chip8.load_rom(&[0x12, 0x00]).unwrap();
+    let options = Options {
+      cycles: Some(100),
+      ..Options::default()
+    };
+    let (mut frames, mut cycles) = (0, 0);
+    run_frames(&mut chip8, &options, None, &mut frames, &mut cycles).unwrap();
+    assert_eq!(cycles, 108);
+    assert_eq!(frames, 9);
+  }
+
+  #[test]
+  fn test_run_frames_error() {
+    let mut chip8 = Chip8::default();
+    // RET with an empty stack
+    chip8.load_rom(&[0x00, 0xEE]).unwrap();
+    let (mut frames, mut cycles) = (0, 0);
+    let result = run_frames(&mut chip8, &Options::default(), None, &mut frames, &mut cycles);
+    assert!(matches!(result, Err(Chip8Error::InterpreterError(_))));
+  }
+
+  #[test]
+  fn test_run_frames_audio() {
+    let mut chip8 = Chip8::default();
+    // LD V0, 0x03; LD ST, V0; JP 0x204
+    chip8.load_rom(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+    let options = Options {
+      frames: Some(6),
+      ..Options::default()
+    };
+    let mut wav = WavSink::new(WAV_SAMPLE_RATE);
+    let (mut frames, mut cycles) = (0, 0);
+    run_frames(&mut chip8, &options, Some(&mut wav), &mut frames, &mut cycles).unwrap();
+    // One frame of samples per frame run, and the beep stops with the timer
+    let samples = wav.samples();
+    assert_eq!(samples.len(), 6 * 735);
+    assert!(samples[..3 * 735].iter().any(|&sample| sample != 0));
+    assert!(samples[3 * 735..].iter().all(|&sample| sample == 0));
+  }
+}
@@ -0,0 +1,42 @@
+use chip8_core::Chip8Key;
+use winit::event::VirtualKeyCode;
+
+// Maps the left hand side of a QWERTY keyboard onto the COSMAC VIP keypad:
+//
+//   1 2 3 4        1 2 3 C
+//   Q W E R   ->   4 5 6 D
+//   A S D F        7 8 9 E
+//   Z X C V        A 0 B F
+pub const KEYMAP: [(VirtualKeyCode, Chip8Key); 16] = [
+  (VirtualKeyCode::Key1, Chip8Key::One),
+  (VirtualKeyCode::Key2, Chip8Key::Two),
+  (VirtualKeyCode::Key3, Chip8Key::Three),
+  (VirtualKeyCode::Key4, Chip8Key::Four),
+  (VirtualKeyCode::Q, Chip8Key::Q),
+  (VirtualKeyCode::W, Chip8Key::W),
+  (VirtualKeyCode::E, Chip8Key::E),
+  (VirtualKeyCode::R, Chip8Key::R),
+  (VirtualKeyCode::A, Chip8Key::A),
+  (VirtualKeyCode::S, Chip8Key::S),
+  (VirtualKeyCode::D, Chip8Key::D),
+  (VirtualKeyCode::F, Chip8Key::F),
+  (VirtualKeyCode::Z, Chip8Key::Z),
+  (VirtualKeyCode::X, Chip8Key::X),
+  (VirtualKeyCode::C, Chip8Key::C),
+  (VirtualKeyCode::V, Chip8Key::V),
+];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_keymap_covers_keypad() {
+    let mut seen = [false; 16];
+    for (_, key) in KEYMAP {
+      assert!(!seen[key.index()]);
+      seen[key.index()] = true;
+    }
+    assert!(seen.iter().all(|&key| key));
+  }
+}
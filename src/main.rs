@@ -1,18 +1,43 @@
-mod interpreter;
+#[cfg(feature = "audio-device")]
+mod audio;
+mod cli;
+mod config;
+mod dap;
+mod debug;
+mod disasm;
 mod error;
+mod gdb;
+mod headless;
+mod keymap;
+mod romdb;
 
 use crate::{
-  interpreter::Chip8,
+  cli::{Cli, Command},
   error::Chip8Error,
 };
+use chip8_core::{
+  assemble,
+  audio::{AudioSink, Sound},
+  Chip8Key,
+  Debugger,
+  Rewinder,
+  Stop,
+  Variant,
+  FRAMES_PER_SECOND,
+  HIRES_HEIGHT,
+  HIRES_WIDTH,
+};
 
 use std::{
-  io::{Read, BufReader},
+  io::{self, Read, BufReader},
   fs::File,
   env::current_dir,
-  time::Instant,
+  path::Path,
+  process::exit,
+  time::{Duration, Instant},
 };
-use log::error;
+use clap::Parser;
+use log::{error, info, warn};
 use pixels::{Pixels, SurfaceTexture};
 use winit::{
   dpi::LogicalSize,
@@ -23,33 +48,116 @@ use winit::{
 use winit_input_helper::WinitInputHelper;
 use native_dialog::FileDialog;
 
+// Hold Backspace to step back through the last ten seconds, one snapshot
+// every REWIND_INTERVAL frames
+const REWIND_INTERVAL: u64 = 4;
+const REWIND_SNAPSHOTS: usize = (10 * FRAMES_PER_SECOND / REWIND_INTERVAL) as usize;
+
+#[cfg(feature = "audio-device")]
+fn open_audio() -> Option<Box<dyn AudioSink>> {
+  match audio::DeviceSink::new() {
+    Ok(sink) => Some(Box::new(sink)),
+    Err(err) => {
+      warn!("{err}");
+      None
+    }
+  }
+}
+
+#[cfg(not(feature = "audio-device"))]
+fn open_audio() -> Option<Box<dyn AudioSink>> {
+  warn!("Built without the audio-device feature, sound is disabled");
+  None
+}
+
+// The variant is picked from the file extension
+pub fn rom_variant(path: &Path) -> Variant {
+  match path.extension().and_then(|ext| ext.to_str()) {
+    Some("sc8") => Variant::SuperChip,
+    Some("xo8") => Variant::XoChip,
+    _ => Variant::Chip8,
+  }
+}
+
+fn window_title(paused: bool) -> &'static str {
+  if paused {
+    "Chip-8 (paused)"
+  } else {
+    "Chip-8"
+  }
+}
+
+// Identifies a ROM in the config file
+pub fn rom_sha1(rom: &[u8]) -> String {
+  sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+// Octo sources (.8o) are assembled on the way in
+pub fn read_rom(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+  let mut reader = BufReader::new(File::open(path)?);
+  let mut buffer = Vec::<u8>::new();
+  reader.read_to_end(&mut buffer)?;
+  if path.extension().is_some_and(|ext| ext == "8o") {
+    let source = String::from_utf8(buffer).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    return Ok(assemble(&source)?);
+  }
+  Ok(buffer)
+}
+
 pub fn main() -> Result<(), Chip8Error> {
   env_logger::init();
 
-  // Chip8 init, load rom
-  let mut chip8 = Chip8::default();
-  let rom = {
-    let cwd = current_dir().unwrap();
-    let path = FileDialog::new()
-      .set_location(&cwd)
-      .add_filter("Chip-8 ROM", &["ch8"])
-      .show_open_single_file()
-      .unwrap();
-    let f = File::open(path.expect("File missing")).expect("Unable to open file");
-    let mut reader = BufReader::new(f);
-    let mut buffer = Vec::<u8>::new();
-    reader.read_to_end(&mut buffer).expect("Unable to read file");
-    buffer
+  let cli = Cli::parse();
+  let result = match &cli.command {
+    Some(Command::Disasm(args)) => Some(disasm::run(args)),
+    Some(Command::Debug(args)) => Some(debug::run(args)),
+    Some(Command::Gdb(args)) => Some(gdb::run(args)),
+    Some(Command::Dap) => Some(dap::run()),
+    // clap only accepts --headless along with a ROM path
+    None if cli.headless => Some(headless::run(&cli, cli.rom.as_deref().unwrap())),
+    None => None,
+  };
+  if let Some(result) = result {
+    if let Err(err) = result {
+      eprintln!("{err}");
+      exit(1);
+    }
+    return Ok(());
+  }
+
+  // Chip8 init, load rom. The dialog is only a fallback for a missing path.
+  let path = match &cli.rom {
+    Some(path) => path.clone(),
+    None => {
+      let cwd = current_dir().unwrap();
+      FileDialog::new()
+        .set_location(&cwd)
+        .add_filter("Chip-8 ROM", &["ch8", "sc8", "xo8", "8o"])
+        .show_open_single_file()
+        .unwrap()
+        .expect("File missing")
+    }
   };
-  chip8.load_rom(&rom)?;
+  let (mut chip8, config) = cli.load(&path)?;
+  let mut debugger = Debugger::new();
+  if cli.paused {
+    debugger.pause();
+  }
+
+  // Audio init
+  let mut audio = open_audio();
 
   // Window init
   let event_loop = EventLoop::new();
   let mut input = WinitInputHelper::new();
+  // The window is scaled from the high resolution display, so hires output
+  // keeps every pixel and lores pixels are drawn 2x2
+  let screen_width = HIRES_WIDTH as u32 * config.scale;
+  let screen_height = HIRES_HEIGHT as u32 * config.scale;
   let window = {
-    let size = LogicalSize::new(Chip8::screen_width(), Chip8::screen_height());
+    let size = LogicalSize::new(screen_width as f32, screen_height as f32);
     WindowBuilder::new()
-      .with_title("Chip-8")
+      .with_title(window_title(debugger.is_paused()))
       .with_inner_size(size)
       .with_min_inner_size(size)
       .build(&event_loop)
@@ -60,25 +168,30 @@ pub fn main() -> Result<(), Chip8Error> {
   let mut pixels = {
     let window_size = window.inner_size();
     let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-    Pixels::new(Chip8::screen_width() as u32, Chip8::screen_height() as u32, surface_texture)?
+    Pixels::new(HIRES_WIDTH as u32, HIRES_HEIGHT as u32, surface_texture)?
   };
 
   // Run event loop
   let mut instant = Instant::now();
+  let mut quick_save: Option<Vec<u8>> = None;
+  let mut rewinder = Rewinder::new(REWIND_SNAPSHOTS, REWIND_INTERVAL);
+  let rewind_step = Duration::from_secs(REWIND_INTERVAL) / FRAMES_PER_SECOND as u32;
+  let mut rewind_elapsed = Duration::ZERO;
+  let mut muted = true;
   event_loop.run(move |event, _, control_flow| {
     if let Event::RedrawRequested(_) = event {
-      // Copy interprefer frame buffer to pixels frame buffer
+      // Copy interpreter frame buffer to pixels frame buffer, which pixels
+      // then scales up to the window
       let frame = chip8.frame();
+      let (width, _) = chip8.resolution();
+      // Lores pixels cover 2x2 of the buffer's
+      let shift = (width < HIRES_WIDTH) as usize;
+      let width = width as usize;
       for (i, pixel) in pixels.frame_mut().chunks_exact_mut(4).enumerate() {
-        let x = (i % Chip8::screen_width() as usize) / Chip8::display_scale() as usize;
-        let y = (i / Chip8::screen_width() as usize) / Chip8::display_scale() as usize;
-        let frame_index = (y * 64) + x;
-        let rgba = if frame[frame_index] == 1 {
-          [0xFF, 0xFF, 0xFF, 0xFF]
-        } else {
-          [0x00, 0x00, 0x00, 0xFF]
-        };
-        pixel.copy_from_slice(&rgba);
+        let x = (i % HIRES_WIDTH as usize) >> shift;
+        let y = (i / HIRES_WIDTH as usize) >> shift;
+        let frame_index = (y * width) + x;
+        pixel.copy_from_slice(&config.palette[frame[frame_index] as usize]);
       }
 
       // Render frame buffer
@@ -91,18 +204,88 @@ pub fn main() -> Result<(), Chip8Error> {
 
     // Handle updates
     if input.update(&event) {
-      if input.key_pressed(VirtualKeyCode::Escape) || input.close_requested() {
+      if input.key_pressed(VirtualKeyCode::Escape) || input.close_requested() || chip8.exited() {
         *control_flow = ControlFlow::Exit;
         return;
       }
 
-      if let Err(err) = chip8.update(&instant.elapsed()) {
-        error!("{err}");
-        *control_flow = ControlFlow::Exit;
-        return;
+      // F5 snapshots the machine, F9 restores the last snapshot
+      if input.key_pressed(VirtualKeyCode::F5) {
+        quick_save = Some(chip8.save_state());
+      }
+      if let Some(state) = quick_save.as_ref().filter(|_| input.key_pressed(VirtualKeyCode::F9)) {
+        if let Err(err) = chip8.load_state(state) {
+          warn!("{err}");
+        }
+      }
+
+      // A key counts as held if any of its bindings is
+      let mut held = [false; 16];
+      for &(virtual_key, key) in &config.keymap {
+        held[key.index()] |= input.key_held(virtual_key);
+      }
+      for key in Chip8Key::ALL {
+        chip8.set_key(key, held[key.index()]);
+      }
+
+      // P pauses and resumes. While paused F6 steps, F7 steps over a CALL and
+      // F8 steps out of the current subroutine.
+      if input.key_pressed(VirtualKeyCode::P) {
+        debugger.toggle_pause();
+        window.set_title(window_title(debugger.is_paused()));
+      }
+      if debugger.is_paused() {
+        if input.key_pressed(VirtualKeyCode::F6) {
+          debugger.step();
+        } else if input.key_pressed(VirtualKeyCode::F7) {
+          debugger.step_over(&chip8);
+        } else if input.key_pressed(VirtualKeyCode::F8) {
+          debugger.step_out(&chip8);
+        }
+      }
+
+      if input.key_held(VirtualKeyCode::Back) {
+        // Step back at the same pace the snapshots were taken
+        rewind_elapsed += instant.elapsed();
+        while rewind_elapsed >= rewind_step {
+          rewind_elapsed -= rewind_step;
+          if let Err(err) = rewinder.rewind(&mut chip8) {
+            warn!("{err}");
+          }
+        }
+      } else {
+        rewind_elapsed = Duration::ZERO;
+        if !debugger.is_paused() {
+          // Sound goes out once per frame run, however often the window redraws
+          let on_frame = |chip8: &_| {
+            if let Some(audio) = audio.as_mut() {
+              audio.play(&Sound::from_chip8(chip8));
+              muted = false;
+            }
+          };
+          if let Some(stop) = debugger.update_frames(&mut chip8, &instant.elapsed(), on_frame) {
+            match stop {
+              // A fault pauses the machine instead of closing the window, so
+              // its state can still be looked at
+              Stop::Fault(_) => error!("{stop}"),
+              _ => info!("{stop}, PC at {:#06x}", chip8.pc()),
+            }
+            window.set_title(window_title(true));
+          }
+          rewinder.record(&chip8);
+        }
+      }
+
+      // The sound timer stands still while paused or rewinding, so mute instead
+      if let Some(audio) = audio.as_mut().filter(|_| !muted) {
+        if debugger.is_paused() || input.key_held(VirtualKeyCode::Back) {
+          audio.play(&Sound::default());
+          muted = true;
+        }
       }
 
       instant = Instant::now();
+      window.request_redraw();
     }
   });
 }
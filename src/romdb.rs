@@ -0,0 +1,78 @@
+use chip8_core::{Chip8Key, Quirks, Variant};
+use winit::event::VirtualKeyCode;
+
+// What's known about a ROM from roms/database, the settings it was made for.
+// Only the ROMs bundled here are in it unless the build was pointed at the
+// full community database, see build.rs.
+pub struct RomInfo {
+  pub title: &'static str,
+  pub variant: Variant,
+  pub quirks: Quirks,
+  // Instructions per frame
+  pub tickrate: Option<usize>,
+  // Colours for each combination of bitplanes, starting with the background
+  pub colors: &'static [[u8; 4]],
+  // Keypad keys the game uses for each action, e.g. ("up", 5)
+  pub keys: &'static [(&'static str, u8)],
+}
+
+// Generated by build.rs
+include!(concat!(env!("OUT_DIR"), "/rom_database.rs"));
+
+// Looks a ROM up by the SHA-1 of its contents, as lowercase hex
+pub fn lookup(sha1: &str) -> Option<&'static RomInfo> {
+  ROMS.get(sha1)
+}
+
+impl RomInfo {
+  // Extra bindings for the actions the game uses, on top of the usual keymap
+  pub fn keymap(&self) -> impl Iterator<Item = (VirtualKeyCode, Chip8Key)> {
+    self.keys.iter().filter_map(|&(action, key)| {
+      let virtual_key = match action {
+        "up" => VirtualKeyCode::Up,
+        "down" => VirtualKeyCode::Down,
+        "left" => VirtualKeyCode::Left,
+        "right" => VirtualKeyCode::Right,
+        "a" => VirtualKeyCode::Space,
+        "b" => VirtualKeyCode::Return,
+        // Second player bindings are left to the keypad
+        _ => return None,
+      };
+      Some((virtual_key, Chip8Key::from_index(key as usize)?))
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{read_rom, rom_sha1};
+  use std::path::Path;
+
+  #[test]
+  fn test_lookup() {
+    let rom = read_rom(Path::new("roms/games/outlaw.ch8")).unwrap();
+    let info = lookup(&rom_sha1(&rom)).unwrap();
+    assert_eq!(info.title, "Outlaw");
+    assert_eq!(info.variant, Variant::Chip8);
+    assert_eq!(info.quirks, Quirks::vip());
+    assert_eq!(info.tickrate, Some(15));
+    assert!(info.keymap().any(|binding| binding == (VirtualKeyCode::Space, Chip8Key::E)));
+    assert!(lookup(&rom_sha1(&[0x12, 0x00])).is_none());
+    // The source assembles to the same ROM, so it's recognised too
+    assert_eq!(read_rom(Path::new("roms/games/outlaw.8o")).unwrap(), rom);
+  }
+
+  #[test]
+  fn test_bundled_roms_known() {
+    for dir in ["roms/games", "roms/test-suite"] {
+      for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "ch8") {
+          let rom = read_rom(&path).unwrap();
+          assert!(lookup(&rom_sha1(&rom)).is_some(), "{} is missing", path.display());
+        }
+      }
+    }
+  }
+}